@@ -1,24 +1,44 @@
-use tauri::AppHandle;
+use tauri::{AppHandle, Manager};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use lazy_static::lazy_static;
-use log::error;
-use rodio::{Decoder, OutputStream, source::Source};
+use log::{error, warn};
+use rodio::{Decoder, OutputStream, source::{Source, SineWave}};
+use rodio::decoder::DecoderError;
+use serde_json::json;
+use crate::event_processing::Payload;
 
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
 pub enum Sound {
     Next,
     Previous,
     Pause,
-    Resume
+    Resume,
+    SongChange
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AudioError {
+    #[error("no sound file is mapped to {0:?}")]
+    Unmapped(Sound),
+    #[error("sound resource not found: {0}")]
+    NotFound(String),
+    #[error("failed to open {path}: {source}")]
+    Io { path: String, source: std::io::Error },
+    #[error("failed to decode {path}: {source}")]
+    Decode { path: String, source: DecoderError },
 }
 
 pub static mut MUTED: bool = false;
 pub static mut VOLUME: f32 = 0.3;
 const AUDIO_DIR: &str = "assets/audio";
 
+// the frontend only needs to hear about a broken install once per session
+static AUDIO_ERROR_REPORTED: AtomicBool = AtomicBool::new(false);
+
 // sounds
 lazy_static! {
     static ref SOUNDS_MAP: HashMap<Sound, &'static str> = {
@@ -30,6 +50,19 @@ lazy_static! {
 
         map
     };
+
+    // pitch (Hz) of the synthesised beep used for sounds without a file, or when a sound file can't be loaded
+    static ref FALLBACK_BEEPS: HashMap<Sound, f32> = {
+        let mut map = HashMap::new();
+        map.insert(Sound::Next, 880.0);
+        map.insert(Sound::Previous, 660.0);
+        map.insert(Sound::Pause, 440.0);
+        map.insert(Sound::Resume, 550.0);
+        // no file on purpose, should stand out from the transpose sounds
+        map.insert(Sound::SongChange, 990.0);
+
+        map
+    };
 }
 
 pub unsafe fn play_sound(name: Sound, app_handle: AppHandle) {
@@ -38,11 +71,14 @@ pub unsafe fn play_sound(name: Sound, app_handle: AppHandle) {
     }
 
     std::thread::spawn(move || {
-        let mut source = match load_sound(name, app_handle) {
-            Some(source) => source,
-            None => {
-                error!("Sound ({:?}) not found.", name);
-                return;
+        let source = match load_sound(name, &app_handle) {
+            Ok(source) => source,
+            Err(AudioError::Unmapped(_)) => fallback_beep(name),
+            Err(err) => {
+                error!("Sound ({:?}) could not be loaded: {}", name, err);
+                report_audio_error(&err, &app_handle);
+
+                fallback_beep(name)
             },
         };
 
@@ -69,25 +105,46 @@ pub unsafe fn play_sound(name: Sound, app_handle: AppHandle) {
         let volume_source = source.amplify(VOLUME);
 
         // Play the sound directly on the device
-        stream_handle.play_raw(volume_source.convert_samples());
+        if let Err(err) = stream_handle.play_raw(volume_source) {
+            error!("{:?}", err);
+            return;
+        }
 
         std::thread::sleep(Duration::from_secs(duration.unwrap().as_secs()));
     });
 }
 
-fn load_sound(name: Sound, app_handle: AppHandle) -> Option<Decoder<BufReader<File>>> {
-    let filename = SOUNDS_MAP.get(&name);
-    if filename.is_none() {
-        return None::<Decoder<BufReader<File>>>;
-    }
+fn load_sound(name: Sound, app_handle: &AppHandle) -> Result<Box<dyn Source<Item = f32> + Send>, AudioError> {
+    let filename = SOUNDS_MAP.get(&name).ok_or(AudioError::Unmapped(name))?;
+    let resource = format!("{}/{}", AUDIO_DIR, filename);
 
-    let path = app_handle.path_resolver().resolve_resource(format!("{}/{}", AUDIO_DIR, filename.unwrap())).unwrap();
+    let path = app_handle.path_resolver().resolve_resource(&resource)
+        .filter(|path| path.exists())
+        .ok_or(AudioError::NotFound(resource))?;
 
     // Load a sound from a file, using a path relative to Cargo.toml
-    let file = BufReader::new(File::open(path).unwrap());
+    let file = File::open(&path).map_err(|source| AudioError::Io { path: path.display().to_string(), source })?;
 
     // Decode that sound file into a source
-    let source = Some(Decoder::new(file).unwrap());
+    let source = Decoder::new(BufReader::new(file))
+        .map_err(|source| AudioError::Decode { path: path.display().to_string(), source })?;
+
+    Ok(Box::new(source.convert_samples()))
+}
+
+fn fallback_beep(name: Sound) -> Box<dyn Source<Item = f32> + Send> {
+    let frequency = FALLBACK_BEEPS.get(&name).cloned().unwrap_or(440.0);
+
+    Box::new(SineWave::new(frequency).take_duration(Duration::from_millis(120)).amplify(0.5))
+}
+
+fn report_audio_error(err: &AudioError, app_handle: &AppHandle) {
+    if AUDIO_ERROR_REPORTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    warn!("Falling back to synthesised sounds, further audio errors will only be logged");
 
-    source
+    let json = serde_json::to_string(&json!({"audio_error": err.to_string()})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
 }
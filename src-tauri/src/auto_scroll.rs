@@ -0,0 +1,103 @@
+use tauri::{AppHandle, Manager};
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use serde_json::json;
+use crate::event_processing::Payload;
+use crate::scroll::scroll_bind_event;
+use crate::settings::SettingsStore;
+use crate::PAUSED;
+
+// how often a waiting auto scroll checks for a pause, a stop or a new speed
+const AUTO_SCROLL_POLL: Duration = Duration::from_millis(20);
+// the faster and slower binds change the time between scrolls by this much
+const SPEED_STEP: f64 = 1.25;
+pub const MIN_AUTO_SCROLL_INTERVAL_MS: u64 = 100;
+pub const MAX_AUTO_SCROLL_INTERVAL_MS: u64 = 30_000;
+
+// time between two scrolls, from settings, the speed binds change it while reading and save it there
+pub static mut AUTO_SCROLL_INTERVAL_MS: u64 = 2000;
+
+pub static mut AUTO_SCROLL_RUNNING: bool = false;
+// bumped every time auto scroll starts or stops, older threads see the change and exit
+static mut AUTO_SCROLL_GENERATION: u64 = 0;
+
+fn emit_auto_scroll(app_handle: &AppHandle) {
+    let (running, interval_ms) = unsafe { (AUTO_SCROLL_RUNNING, AUTO_SCROLL_INTERVAL_MS) };
+
+    let json = serde_json::to_string(&json!({"auto_scroll": {"running": running, "interval_ms": interval_ms}})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+}
+
+pub unsafe fn toggle_auto_scroll(app_handle: &AppHandle) {
+    match AUTO_SCROLL_RUNNING {
+        true => stop_auto_scroll(app_handle),
+        false => start_auto_scroll(app_handle),
+    }
+}
+
+pub unsafe fn stop_auto_scroll(app_handle: &AppHandle) {
+    if !AUTO_SCROLL_RUNNING {
+        return;
+    }
+
+    AUTO_SCROLL_GENERATION += 1;
+    AUTO_SCROLL_RUNNING = false;
+    info!("Auto scroll stopped");
+
+    emit_auto_scroll(app_handle);
+}
+
+// scrolls down one scroll bind's worth every AUTO_SCROLL_INTERVAL_MS, holding still while paused
+pub unsafe fn start_auto_scroll(app_handle: &AppHandle) {
+    AUTO_SCROLL_GENERATION += 1;
+    AUTO_SCROLL_RUNNING = true;
+    let generation = AUTO_SCROLL_GENERATION;
+
+    emit_auto_scroll(app_handle);
+
+    std::thread::spawn(move || unsafe {
+        info!("Auto scroll started, every {}ms", AUTO_SCROLL_INTERVAL_MS);
+
+        let mut last_scroll = Instant::now();
+
+        while AUTO_SCROLL_GENERATION == generation {
+            // resuming waits a whole interval, the reader just got back to the sheet
+            if PAUSED {
+                last_scroll = Instant::now();
+                std::thread::sleep(AUTO_SCROLL_POLL);
+                continue;
+            }
+
+            // read every time round, so a new speed applies to the scroll already waiting
+            let next_scroll = last_scroll + Duration::from_millis(AUTO_SCROLL_INTERVAL_MS);
+            let now = Instant::now();
+            if now < next_scroll {
+                std::thread::sleep((next_scroll - now).min(AUTO_SCROLL_POLL));
+                continue;
+            }
+
+            scroll_bind_event(-1);
+            last_scroll = Instant::now();
+        }
+    });
+}
+
+// the faster and slower binds, faster is a shorter time between scrolls
+pub unsafe fn change_auto_scroll_speed(faster: bool, app_handle: &AppHandle) {
+    let interval_ms = match faster {
+        true => AUTO_SCROLL_INTERVAL_MS as f64 / SPEED_STEP,
+        false => AUTO_SCROLL_INTERVAL_MS as f64 * SPEED_STEP,
+    };
+
+    AUTO_SCROLL_INTERVAL_MS = (interval_ms.round() as u64).clamp(MIN_AUTO_SCROLL_INTERVAL_MS, MAX_AUTO_SCROLL_INTERVAL_MS);
+    info!("Auto scroll every {}ms", AUTO_SCROLL_INTERVAL_MS);
+
+    // kept for next time, and so applying other settings doesn't put the old speed back
+    if let Some(store) = app_handle.try_state::<SettingsStore>() {
+        if let Err(err) = store.patch(&json!({"scroll": {"autoIntervalMs": AUTO_SCROLL_INTERVAL_MS}})) {
+            warn!("Failed to save auto scroll speed: {}", err);
+        }
+    }
+
+    emit_auto_scroll(app_handle);
+}
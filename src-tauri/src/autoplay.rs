@@ -0,0 +1,356 @@
+use tauri::AppHandle;
+use tauri::Manager;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use lazy_static::lazy_static;
+use log::{error, info};
+use rdev::{simulate, EventType, Key};
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::event_processing::Payload;
+use crate::history;
+use crate::metronome::sleep_until;
+use crate::layout::{Keystroke, Modifier, VP_61};
+use crate::sheet_parser::parse_sheet;
+use crate::sheet_positions::emit_current_index;
+use crate::worker::{queue_keys, transpose_and_wait};
+use crate::{PAUSED, SELECTED_INDEX, TRANSPOSES};
+
+// how long a key is held down, short enough for the fastest steps we allow
+const KEY_HOLD: Duration = Duration::from_millis(15);
+// how often a paused or waiting autoplay checks whether it can carry on
+const PAUSE_POLL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, thiserror::Error)]
+pub enum AutoplayError {
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl Serialize for AutoplayError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    // pressed together, one key is a single note
+    Keys(Vec<char>),
+    Rest,
+    // index is the marker's place among the sheet's transposes
+    Transpose { index: usize, transpose: i32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetStep {
+    pub step: Step,
+    // character offset into the sheet, same as sheet positions
+    pub offset: usize,
+}
+
+struct Autoplay {
+    steps: Vec<SheetStep>,
+    position: usize,
+    step_duration: Duration,
+    // the game's transpose has to catch up after a seek
+    seeked: bool,
+}
+
+// bumped every time autoplay starts or stops, older threads see the change and exit
+static mut AUTOPLAY_GENERATION: u64 = 0;
+
+lazy_static! {
+    static ref AUTOPLAY: Mutex<Option<Autoplay>> = Mutex::new(None);
+}
+
+// a VP sheet as steps: keys, "[chords]", "|" or "-" for rests and transpose markers
+// anything else, spaces and line breaks included, takes no time
+pub fn parse_steps(text: &str) -> Vec<SheetStep> {
+    let markers = parse_sheet(text).markers;
+    let chars: Vec<char> = text.chars().collect();
+
+    let mut steps = vec![];
+    let mut markers = markers.iter().enumerate().peekable();
+    let mut offset = 0;
+
+    while offset < chars.len() {
+        if let Some((index, marker)) = markers.peek().filter(|(_, marker)| marker.start == offset).cloned() {
+            steps.push(SheetStep { step: Step::Transpose { index, transpose: marker.transpose }, offset });
+            offset = marker.end;
+            markers.next();
+            continue;
+        }
+
+        let step = match chars[offset] {
+            // chords don't span lines
+            '[' => match chars[offset + 1..].iter().position(|c| *c == ']' || *c == '[' || *c == '\n') {
+                Some(length) if chars[offset + 1 + length] == ']' => {
+                    let keys: Vec<char> = chars[offset + 1..offset + 1 + length]
+                        .iter()
+                        .cloned()
+                        .filter(|c| VP_61.pitch_for_char(*c).is_some())
+                        .collect();
+
+                    if !keys.is_empty() {
+                        steps.push(SheetStep { step: Step::Keys(keys), offset });
+                    }
+                    offset += length + 2;
+                    continue;
+                }
+                // an unclosed "[" is ignored, the keys after it are played one by one
+                _ => None,
+            },
+            '|' | '-' => Some(Step::Rest),
+            c if VP_61.pitch_for_char(c).is_some() => Some(Step::Keys(vec![c])),
+            _ => None,
+        };
+
+        if let Some(step) = step {
+            steps.push(SheetStep { step, offset });
+        }
+        offset += 1;
+    }
+
+    steps
+}
+
+// the key typing a layout key's unshifted character on a us layout, 'q' is KeyQ and '1' is Num1
+pub fn key_for_layout_key(key: char) -> Option<Key> {
+    let name = match key {
+        key if key.is_ascii_digit() => format!("Num{}", key),
+        key => format!("Key{}", key.to_ascii_uppercase()),
+    };
+
+    serde_json::from_value(Value::String(name)).ok()
+}
+
+// VP keys are typed on a us layout, black keys with shift
+fn key_for_char(c: char) -> Option<(Key, bool)> {
+    let keystroke = Keystroke::from_char(c)?;
+
+    Some((key_for_layout_key(keystroke.key)?, keystroke.modifier == Modifier::Shift))
+}
+
+fn send(event_type: EventType) {
+    if let Err(err) = simulate(&event_type) {
+        error!("Failed to send {:?}: {:?}", event_type, err);
+    }
+}
+
+// unshifted keys go down first, shift is only held while the shifted ones are pressed
+// runs on the key worker, so the keys don't interleave with a transpose or macro
+pub fn play_keys(keys: &[char]) {
+    let keys: Vec<(Key, bool)> = keys.iter().filter_map(|c| key_for_char(*c)).collect();
+
+    for (key, _) in keys.iter().filter(|(_, shift)| !shift) {
+        send(EventType::KeyPress(*key));
+    }
+
+    if keys.iter().any(|(_, shift)| *shift) {
+        send(EventType::KeyPress(Key::ShiftLeft));
+        for (key, _) in keys.iter().filter(|(_, shift)| *shift) {
+            send(EventType::KeyPress(*key));
+        }
+        send(EventType::KeyRelease(Key::ShiftLeft));
+    }
+
+    std::thread::sleep(KEY_HOLD);
+
+    for (key, _) in &keys {
+        send(EventType::KeyRelease(*key));
+    }
+}
+
+// transposes the game like the next/previous binds would, selecting the transpose when the list is the sheet's
+unsafe fn play_transpose(index: usize, new_transpose: i32, app_handle: &AppHandle) {
+    // the next notes must be played transposed
+    transpose_and_wait(new_transpose);
+    history::forget_redo(app_handle);
+
+    let is_sheet_transpose = TRANSPOSES.lock().unwrap().get(index) == Some(&new_transpose);
+    if is_sheet_transpose {
+        *SELECTED_INDEX.lock().unwrap() = index;
+        emit_current_index(index, app_handle);
+    }
+}
+
+fn emit_autoplay(state: &str, offset: Option<usize>, app_handle: &AppHandle) {
+    let json = serde_json::to_string(&json!({"autoplay": {"state": state, "position": offset}})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+}
+
+pub unsafe fn start_autoplay_thread(app_handle: AppHandle) {
+    AUTOPLAY_GENERATION += 1;
+    let generation = AUTOPLAY_GENERATION;
+
+    std::thread::spawn(move || {
+        info!("Autoplay started");
+
+        let mut was_paused = None;
+        let mut next_step = Instant::now();
+
+        while AUTOPLAY_GENERATION == generation {
+            // the pause bind pauses and resumes autoplay too
+            if PAUSED {
+                if was_paused != Some(true) {
+                    emit_autoplay("paused", None, &app_handle);
+                    was_paused = Some(true);
+                }
+
+                std::thread::sleep(PAUSE_POLL);
+                next_step = Instant::now();
+                continue;
+            }
+            was_paused = Some(false);
+
+            let (sheet_step, step_duration, catch_up) = {
+                let mut autoplay = AUTOPLAY.lock().unwrap();
+                let autoplay = match autoplay.as_mut() {
+                    Some(autoplay) => autoplay,
+                    None => break,
+                };
+
+                // after a seek, the transpose in effect is the last marker before the new position
+                let catch_up = match autoplay.seeked {
+                    true => autoplay.steps[..autoplay.position].iter().rev().find_map(|sheet_step| match sheet_step.step {
+                        Step::Transpose { index, transpose } => Some((index, transpose)),
+                        _ => None,
+                    }),
+                    false => None,
+                };
+                autoplay.seeked = false;
+
+                let sheet_step = autoplay.steps.get(autoplay.position).cloned();
+                autoplay.position += 1;
+
+                (sheet_step, autoplay.step_duration, catch_up)
+            };
+
+            if let Some((index, transpose)) = catch_up {
+                play_transpose(index, transpose, &app_handle);
+            }
+
+            let sheet_step = match sheet_step {
+                Some(sheet_step) => sheet_step,
+                None => {
+                    let mut autoplay = AUTOPLAY.lock().unwrap();
+                    // a new autoplay may have started while this one played its last step
+                    if AUTOPLAY_GENERATION == generation {
+                        *autoplay = None;
+                        emit_autoplay("finished", None, &app_handle);
+                    }
+                    break;
+                }
+            };
+
+            emit_autoplay("playing", Some(sheet_step.offset), &app_handle);
+
+            match sheet_step.step {
+                Step::Keys(keys) => queue_keys(keys),
+                Step::Rest => {}
+                // transposing takes no time in the song, the next step follows straight away
+                Step::Transpose { index, transpose } => {
+                    play_transpose(index, transpose, &app_handle);
+                    continue;
+                }
+            }
+
+            next_step += step_duration;
+            sleep_until(next_step);
+        }
+
+        info!("Autoplay stopped");
+    });
+}
+
+#[tauri::command]
+pub fn start_autoplay(app_handle: AppHandle, text: String, bpm: f64, steps_per_beat: Option<u32>) -> Result<(), AutoplayError> {
+    if !(20.0..=400.0).contains(&bpm) {
+        return Err(AutoplayError::Invalid("Tempo must be between 20 and 400 bpm".to_string()));
+    }
+
+    let steps = parse_steps(&text);
+    if !steps.iter().any(|sheet_step| matches!(sheet_step.step, Step::Keys(_))) {
+        return Err(AutoplayError::Invalid("The sheet has no notes to play".to_string()));
+    }
+
+    let step_duration = Duration::from_secs_f64(60.0 / bpm / steps_per_beat.unwrap_or(2).max(1) as f64);
+    *AUTOPLAY.lock().unwrap() = Some(Autoplay { steps, position: 0, step_duration, seeked: false });
+
+    // waits for the pause bind when the app is paused
+    unsafe { start_autoplay_thread(app_handle); }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn stop_autoplay(app_handle: AppHandle) {
+    unsafe { AUTOPLAY_GENERATION += 1; }
+    *AUTOPLAY.lock().unwrap() = None;
+
+    emit_autoplay("stopped", None, &app_handle);
+}
+
+// position is a character offset into the sheet, playing carries on from the first step at or after it
+#[tauri::command]
+pub fn seek_autoplay(position: usize) -> Result<(), AutoplayError> {
+    let mut autoplay = AUTOPLAY.lock().unwrap();
+    let autoplay = autoplay.as_mut().ok_or(AutoplayError::Invalid("Autoplay is not running".to_string()))?;
+
+    autoplay.position = autoplay.steps.iter()
+        .position(|sheet_step| sheet_step.offset >= position)
+        .unwrap_or(autoplay.steps.len());
+    autoplay.seeked = true;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(text: &str) -> Vec<(Step, usize)> {
+        parse_steps(text).into_iter().map(|sheet_step| (sheet_step.step, sheet_step.offset)).collect()
+    }
+
+    fn keys(keys: &str) -> Step {
+        Step::Keys(keys.chars().collect())
+    }
+
+    #[test]
+    fn single_keys_and_chords() {
+        assert_eq!(steps("t [tu] y"), vec![(keys("t"), 0), (keys("tu"), 2), (keys("y"), 7)]);
+        // what isn't a key inside a chord is skipped, a chord of nothing isn't a step
+        assert_eq!(steps("[t u] [ ]"), vec![(keys("tu"), 0)]);
+        // t isn't a marker here
+        assert_eq!(steps("[t0]"), vec![(keys("t0"), 0)]);
+    }
+
+    #[test]
+    fn unclosed_chords_play_key_by_key() {
+        assert_eq!(steps("[ty\nu]"), vec![(keys("t"), 1), (keys("y"), 2), (keys("u"), 4)]);
+        assert_eq!(steps("[t[y]"), vec![(keys("t"), 1), (keys("y"), 2)]);
+        assert_eq!(steps("t ["), vec![(keys("t"), 0)]);
+    }
+
+    #[test]
+    fn bars_and_dashes_are_rests() {
+        assert_eq!(steps("t|-y"), vec![(keys("t"), 0), (Step::Rest, 1), (Step::Rest, 2), (keys("y"), 3)]);
+    }
+
+    #[test]
+    fn markers_become_transposes_at_their_offsets() {
+        assert_eq!(steps("[T+2] t (transpose: -1) y"), vec![
+            (Step::Transpose { index: 0, transpose: 2 }, 0),
+            (keys("t"), 6),
+            (Step::Transpose { index: 1, transpose: -1 }, 8),
+            (keys("y"), 24),
+        ]);
+    }
+
+    #[test]
+    fn offsets_count_characters() {
+        assert_eq!(steps("é♪ t"), vec![(keys("t"), 3)]);
+    }
+}
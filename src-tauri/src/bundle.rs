@@ -0,0 +1,442 @@
+use tauri::{AppHandle, Manager, State};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use log::{info, warn};
+use rusqlite::Connection;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Map, Value};
+use zip::write::FileOptions;
+use crate::database::{Database, DatabaseError, unix_timestamp};
+use crate::event_processing::Payload;
+use crate::keyboard::keycode_from_key_name;
+use crate::macros::KeyMacro;
+use crate::profiles::{self, KeybindProfile};
+use crate::settings::{Settings, SettingsError, SettingsStore};
+use crate::setlists;
+use crate::sheets::{self, Sheet};
+
+const BUNDLE_FORMAT: &str = "multi-transpose-profile";
+const BUNDLE_VERSION: u32 = 1;
+// name of the json inside a .zip bundle, images sit next to it under images/
+const BUNDLE_ENTRY: &str = "profile.json";
+const IMAGES_DIR: &str = "sheet-images";
+
+#[derive(Debug, thiserror::Error)]
+pub enum BundleError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    Settings(#[from] SettingsError),
+    #[error("not a multi-transpose profile, or from a newer version")]
+    Unsupported,
+}
+
+impl Serialize for BundleError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundledBind {
+    // platform independent rdev key name
+    pub key: String,
+    // the exporting machine's keycode, only used when the key name can't be resolved here
+    pub key_code: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundledKeybindProfile {
+    pub name: String,
+    pub is_default: bool,
+    pub binds: Map<String, Value>,
+    // bundles from before macros have none
+    #[serde(default)]
+    pub macros: Vec<KeyMacro>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundledSheet {
+    #[serde(flatten)]
+    pub sheet: Sheet,
+    // entry in a .zip bundle holding the sheet's image
+    #[serde(default)]
+    pub image_entry: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundledSetlist {
+    pub name: String,
+    // positions in the bundle's sheets, in performance order, sheet ids mean nothing on another machine
+    pub sheets: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: i64,
+    pub keybinds: Vec<BundledKeybindProfile>,
+    pub settings: Settings,
+    // None when exported without the sheet library
+    #[serde(default)]
+    pub sheets: Option<Vec<BundledSheet>>,
+    // only exported with the sheet library, bundles from before setlists have none
+    #[serde(default)]
+    pub setlists: Option<Vec<BundledSetlist>>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    // imported entries win, everything else is kept
+    Merge,
+    // everything the bundle contains is removed first
+    Replace,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPreview {
+    pub keybind_profiles: Vec<String>,
+    // profiles that already exist here, overwritten on merge
+    pub keybind_conflicts: Vec<String>,
+    // binds whose key has no name rdev knows, their keycode may mean another key on this platform
+    pub unportable_binds: Vec<String>,
+    pub settings_changed: bool,
+    pub sheets: usize,
+    // "title - artist" of sheets already in the library, skipped on merge
+    pub sheet_conflicts: Vec<String>,
+    pub setlists: usize,
+    // setlists already here with the same name, skipped on merge
+    pub setlist_conflicts: Vec<String>,
+    // setlists here that a replace removes, the bundle brings its sheets but not these
+    pub removed_setlists: Vec<String>,
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension().map(|extension| extension.eq_ignore_ascii_case("zip")).unwrap_or(false)
+}
+
+fn sheet_key(sheet: &Sheet) -> String {
+    format!("{} - {}", sheet.title, sheet.artist.clone().unwrap_or_default())
+}
+
+fn bundle_profile(profile: &KeybindProfile) -> BundledKeybindProfile {
+    let mut binds = Map::new();
+
+    if let Some(keys) = profile.keys.as_object() {
+        for (name, bind) in keys {
+            let value = bind.get("value");
+            let key = value.and_then(|value| value.get("key")).and_then(|key| key.as_str());
+            let key_code = value.and_then(|value| value.get("keyCode")).and_then(|code| code.as_u64());
+
+            if let (Some(key), Some(key_code)) = (key, key_code) {
+                binds.insert(name.clone(), json!(BundledBind { key: key.to_string(), key_code }));
+            }
+        }
+    }
+
+    BundledKeybindProfile { name: profile.name.clone(), is_default: profile.is_default, binds, macros: profile.macros.clone() }
+}
+
+// rebuilds the frontend's keys object, resolving every key name to this platform's keycode
+fn unbundle_profile(profile: &BundledKeybindProfile, unportable: &mut Vec<String>) -> KeybindProfile {
+    let mut keys = Map::new();
+
+    for (name, bind) in &profile.binds {
+        let bind: BundledBind = match serde_json::from_value(bind.clone()) {
+            Ok(bind) => bind,
+            Err(_) => continue,
+        };
+
+        let key_code = keycode_from_key_name(&bind.key).unwrap_or_else(|| {
+            unportable.push(format!("{}: {}", profile.name, name));
+            bind.key_code
+        });
+
+        keys.insert(name.clone(), json!({"value": {"key": bind.key, "keyCode": key_code}}));
+    }
+
+    // a macro's key resolves the same way a bind's does, its steps are key names already
+    let macros = profile.macros.iter().cloned().map(|mut key_macro| {
+        if let Some(key) = &key_macro.key {
+            key_macro.key_code = keycode_from_key_name(key).or_else(|| {
+                unportable.push(format!("{}: {}", profile.name, key_macro.name));
+                key_macro.key_code
+            });
+        }
+        key_macro
+    }).collect();
+
+    KeybindProfile { name: profile.name.clone(), keys: Value::Object(keys), is_default: profile.is_default, macros }
+}
+
+fn read_bundle(path: &Path) -> Result<(Bundle, Option<zip::ZipArchive<File>>), BundleError> {
+    let mut contents = String::new();
+    let mut archive = None;
+
+    if is_zip(path) {
+        let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+        zip.by_name(BUNDLE_ENTRY)?.read_to_string(&mut contents)?;
+        archive = Some(zip);
+    }
+    else {
+        File::open(path)?.read_to_string(&mut contents)?;
+    }
+
+    let bundle: Bundle = serde_json::from_str(&contents)?;
+    if bundle.format != BUNDLE_FORMAT || bundle.version > BUNDLE_VERSION {
+        return Err(BundleError::Unsupported);
+    }
+
+    Ok((bundle, archive))
+}
+
+pub fn export(conn: &Connection, settings: Settings, path: &Path, include_sheets: bool) -> Result<(), BundleError> {
+    let keybinds = profiles::list_profiles(conn)?
+        .iter()
+        .filter_map(|summary| profiles::get_profile(conn, &summary.name).transpose())
+        .map(|profile| profile.map(|profile| bundle_profile(&profile)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let zip = is_zip(path);
+    let mut images = vec![];
+
+    let (sheets, setlists) = if include_sheets {
+        let mut bundled = vec![];
+        for sheet in sheets::find_sheets(conn, None, None)? {
+            let mut image_entry = None;
+
+            // images only travel in zips, a json bundle keeps the path as is
+            if let (true, Some(image_path)) = (zip, sheet.image_path.as_ref()) {
+                let image_path = PathBuf::from(image_path);
+                if image_path.exists() {
+                    let file_name = image_path.file_name().unwrap().to_string_lossy();
+                    let entry = format!("images/{}-{}", sheet.id.unwrap(), file_name);
+                    images.push((entry.clone(), image_path));
+                    image_entry = Some(entry);
+                }
+            }
+
+            bundled.push(BundledSheet { sheet, image_entry });
+        }
+
+        let positions: HashMap<i64, usize> = bundled.iter().enumerate().map(|(position, bundled)| (bundled.sheet.id.unwrap(), position)).collect();
+        let setlists = setlists::all_setlists(conn)?.into_iter().map(|setlist| BundledSetlist {
+            sheets: setlist.sheet_ids.iter().filter_map(|id| positions.get(id).copied()).collect(),
+            name: setlist.name,
+        }).collect();
+
+        (Some(bundled), Some(setlists))
+    }
+    else {
+        (None, None)
+    };
+
+    let bundle = Bundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        exported_at: unix_timestamp(),
+        keybinds,
+        settings,
+        sheets,
+        setlists,
+    };
+    let json = serde_json::to_string_pretty(&bundle)?;
+
+    if zip {
+        let mut writer = zip::ZipWriter::new(File::create(path)?);
+        let options = FileOptions::default();
+
+        writer.start_file(BUNDLE_ENTRY, options)?;
+        writer.write_all(json.as_bytes())?;
+
+        for (entry, image_path) in images {
+            writer.start_file(entry, options)?;
+            writer.write_all(&std::fs::read(image_path)?)?;
+        }
+
+        writer.finish()?;
+    }
+    else {
+        std::fs::write(path, json)?;
+    }
+
+    info!("Exported profile to {}", path.display());
+
+    Ok(())
+}
+
+pub fn preview(conn: &Connection, current_settings: &Settings, path: &Path) -> Result<ImportPreview, BundleError> {
+    let (bundle, _) = read_bundle(path)?;
+    let mut preview = ImportPreview::default();
+
+    let existing: HashSet<String> = profiles::list_profiles(conn)?.into_iter().map(|profile| profile.name).collect();
+    for profile in &bundle.keybinds {
+        preview.keybind_profiles.push(profile.name.clone());
+        if existing.contains(&profile.name) {
+            preview.keybind_conflicts.push(profile.name.clone());
+        }
+
+        unbundle_profile(profile, &mut preview.unportable_binds);
+    }
+
+    preview.settings_changed = serde_json::to_value(&bundle.settings)? != serde_json::to_value(current_settings)?;
+
+    if let Some(bundled_sheets) = &bundle.sheets {
+        let existing: HashSet<String> = sheets::find_sheets(conn, None, None)?.iter().map(sheet_key).collect();
+
+        preview.sheets = bundled_sheets.len();
+        preview.sheet_conflicts = bundled_sheets.iter()
+            .map(|bundled| sheet_key(&bundled.sheet))
+            .filter(|key| existing.contains(key))
+            .collect();
+
+        let existing: Vec<String> = setlists::all_setlists(conn)?.into_iter().map(|setlist| setlist.name).collect();
+        let bundled_setlists = bundle.setlists.unwrap_or_default();
+
+        preview.setlists = bundled_setlists.len();
+        preview.setlist_conflicts = bundled_setlists.iter()
+            .map(|setlist| setlist.name.clone())
+            .filter(|name| existing.contains(name))
+            .collect();
+        // a replace clears the library, setlists can't keep sheets that are gone
+        preview.removed_setlists = existing.into_iter()
+            .filter(|name| !bundled_setlists.iter().any(|setlist| &setlist.name == name))
+            .collect();
+    }
+
+    Ok(preview)
+}
+
+pub fn import(conn: &mut Connection, settings_store: &SettingsStore, images_dir: &Path, path: &Path, mode: ImportMode) -> Result<(), BundleError> {
+    let (bundle, mut archive) = read_bundle(path)?;
+
+    // nothing is removed for a bundle whose settings would be refused afterwards
+    bundle.settings.validate()?;
+
+    // a bad profile or sheet halfway through leaves the library as it was
+    let tx = conn.transaction().map_err(DatabaseError::from)?;
+
+    if let ImportMode::Replace = mode {
+        tx.execute("DELETE FROM KeyBindConfig", []).map_err(DatabaseError::from)?;
+        if bundle.sheets.is_some() {
+            // deleting the sheets would leave every setlist empty, the bundle's setlists take their place
+            tx.execute("DELETE FROM Setlist", []).map_err(DatabaseError::from)?;
+            tx.execute("DELETE FROM Sheet", []).map_err(DatabaseError::from)?;
+        }
+    }
+
+    let mut unportable = vec![];
+    for profile in &bundle.keybinds {
+        profiles::put_profile(&tx, &unbundle_profile(profile, &mut unportable))?;
+    }
+
+    if !unportable.is_empty() {
+        warn!("Imported binds without a portable key name: {:?}", unportable);
+    }
+
+    if let Some(bundled_sheets) = bundle.sheets {
+        let existing: HashMap<String, i64> = sheets::find_sheets(&tx, None, None)?.iter()
+            .map(|sheet| (sheet_key(sheet), sheet.id.unwrap()))
+            .collect();
+        // library id of each bundled sheet by its position in the bundle, for the setlists
+        let mut sheet_ids = vec![];
+
+        for BundledSheet { mut sheet, image_entry } in bundled_sheets {
+            if let Some(id) = existing.get(&sheet_key(&sheet)) {
+                sheet_ids.push(Some(*id));
+                continue;
+            }
+
+            if let (Some(entry), Some(archive)) = (image_entry, archive.as_mut()) {
+                // only the file name is trusted, the entry could point anywhere with ".."
+                let file_name = match Path::new(&entry).file_name() {
+                    Some(file_name) => file_name.to_owned(),
+                    None => {
+                        sheet_ids.push(None);
+                        continue;
+                    }
+                };
+
+                std::fs::create_dir_all(images_dir)?;
+                let image_path = images_dir.join(file_name);
+
+                std::io::copy(&mut archive.by_name(&entry)?, &mut File::create(&image_path)?)?;
+                sheet.image_path = Some(image_path.to_string_lossy().to_string());
+            }
+
+            sheet.id = None;
+            sheet_ids.push(Some(sheets::write_sheet(&tx, &sheet)?));
+        }
+
+        let existing: HashSet<String> = setlists::all_setlists(&tx)?.into_iter().map(|setlist| setlist.name).collect();
+        for setlist in bundle.setlists.unwrap_or_default() {
+            if existing.contains(&setlist.name) {
+                continue;
+            }
+
+            let ids: Vec<i64> = setlist.sheets.iter().filter_map(|&position| sheet_ids.get(position).copied().flatten()).collect();
+            setlists::insert_setlist(&tx, &setlist.name, &ids)?;
+        }
+    }
+
+    tx.commit().map_err(DatabaseError::from)?;
+
+    // validated above, so the library isn't replaced without its settings
+    match mode {
+        ImportMode::Replace => settings_store.replace(bundle.settings)?,
+        ImportMode::Merge => settings_store.patch(&serde_json::to_value(&bundle.settings)?)?,
+    };
+
+    info!("Imported profile from {}", path.display());
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn export_profile(database: State<Database>, settings: State<SettingsStore>, path: String, include_sheets: bool) -> Result<(), BundleError> {
+    let conn = database.conn()?;
+
+    export(&conn, settings.get(), Path::new(&path), include_sheets)
+}
+
+#[tauri::command]
+pub fn preview_profile_import(database: State<Database>, settings: State<SettingsStore>, path: String) -> Result<ImportPreview, BundleError> {
+    let conn = database.conn()?;
+
+    preview(&conn, &settings.get(), Path::new(&path))
+}
+
+#[tauri::command]
+pub fn import_profile(app_handle: AppHandle, database: State<Database>, settings: State<SettingsStore>, path: String, mode: ImportMode) -> Result<(), BundleError> {
+    let mut conn = database.conn()?;
+    let images_dir = app_handle.path_resolver().app_data_dir().unwrap_or_default().join(IMAGES_DIR);
+
+    import(&mut conn, &settings, &images_dir, Path::new(&path), mode)?;
+
+    // the imported profile and settings take effect straight away
+    unsafe {
+        settings.get().apply();
+        profiles::restore_active_profile(&conn, &app_handle);
+    }
+
+    let json = serde_json::to_string(&json!({"profile_imported": true})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+
+    Ok(())
+}
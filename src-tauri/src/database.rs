@@ -0,0 +1,136 @@
+use tauri::AppHandle;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::info;
+use rusqlite::{params, Connection};
+
+// same file the sql plugin opens for "sqlite:multi_transpose.db", so existing user data is kept
+const DATABASE_FILE: &str = "multi_transpose.db";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DatabaseError {
+    #[error("app config directory could not be resolved")]
+    NoConfigDir,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("migration {version} ({description}) failed: {source}")]
+    Migration { version: i64, description: &'static str, source: rusqlite::Error },
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("database could not be opened: {0}")]
+    Unavailable(String),
+}
+
+// so commands can return DatabaseError directly to the frontend
+impl serde::Serialize for DatabaseError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+// append new schema changes here as the next numbered file in ./migrations, never edit an applied one
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "init_tables",
+        sql: include_str!("./migrations/0001_init_tables.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "sheet_library",
+        sql: include_str!("./migrations/0002_sheet_library.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "setlists",
+        sql: include_str!("./migrations/0003_setlists.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "transpose_annotations",
+        sql: include_str!("./migrations/0004_transpose_annotations.sql"),
+    },
+];
+
+// managed by tauri, get it with app_handle.state::<Database>()
+// holds why opening failed instead of a connection, so commands return that rather than tauri panicking on missing state
+pub struct Database(Result<Mutex<Connection>, String>);
+
+impl Database {
+    pub fn new(conn: Result<Connection, DatabaseError>) -> Self {
+        Database(conn.map(Mutex::new).map_err(|err| err.to_string()))
+    }
+
+    pub fn conn(&self) -> Result<MutexGuard<Connection>, DatabaseError> {
+        match &self.0 {
+            Ok(conn) => Ok(conn.lock().unwrap()),
+            Err(err) => Err(DatabaseError::Unavailable(err.clone())),
+        }
+    }
+}
+
+pub fn database_path(app_handle: &AppHandle) -> Result<PathBuf, DatabaseError> {
+    let dir = app_handle.path_resolver().app_config_dir().ok_or(DatabaseError::NoConfigDir)?;
+    std::fs::create_dir_all(&dir)?;
+
+    Ok(dir.join(DATABASE_FILE))
+}
+
+pub fn open(app_handle: &AppHandle) -> Result<Connection, DatabaseError> {
+    let mut conn = Connection::open(database_path(app_handle)?)?;
+    // sqlite leaves ON DELETE CASCADE off unless asked per connection
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    run_migrations(&mut conn)?;
+
+    Ok(conn)
+}
+
+pub fn run_migrations(conn: &mut Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS SchemaVersion (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            appliedAt INTEGER NOT NULL
+        );"
+    )?;
+
+    let current_version: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM SchemaVersion",
+        [],
+        |row| row.get(0),
+    )?;
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > current_version) {
+        info!("Running migration {} ({})", migration.version, migration.description);
+
+        // each migration is applied together with its version row, or not at all
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql).map_err(|source| DatabaseError::Migration {
+            version: migration.version,
+            description: migration.description,
+            source,
+        })?;
+        tx.execute(
+            "INSERT INTO SchemaVersion (version, description, appliedAt) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.description, unix_timestamp()],
+        )?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+pub fn unix_timestamp() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
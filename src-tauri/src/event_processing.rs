@@ -4,9 +4,15 @@ use log::info;
 use tauri::{AppHandle, Event, Manager};
 use serde_json::{json, Value};
 use serde_json::Value::Object;
-use crate::keyboard::{KEY_LISTEN, PAUSE_BIND, TRANSPOSE_DOWN_BIND, previous_transpose_bind_fn, TRANSPOSE_UP_BIND, next_transpose_bind_fn, PREVIOUS_TRANSPOSE_BIND, NEXT_TRANSPOSE_BIND, SCROLL_DOWN_BIND};
+use crate::keyboard::{KEY_LISTEN, previous_transpose_bind_fn, next_transpose_bind_fn, set_bind};
 use crate::audio::{MUTED, VOLUME};
-use crate::{PAUSED, SELECTED_INDEX, TRANSPOSES, CURRENT_TRANSPOSE, SCROLL_VALUE};
+use crate::history;
+use crate::metronome::metronome_event;
+use crate::practice_loop::practice_loop_event;
+use crate::sheet_positions::{emit_current_index, sheet_text_event};
+use crate::sustain::release_sustain;
+use crate::worker::queue_resync;
+use crate::{PAUSED, SELECTED_INDEX, TRANSPOSES, SCROLL_VALUE, replace_transposes, TransposeAnnotation};
 use rdev::{simulate, EventType};
 
 #[derive(Clone, serde::Serialize)]
@@ -14,15 +20,25 @@ pub struct Payload {
     pub message: String,
 }
 
+// a transposes entry is a number, or {"transpose": 3, "label": "Bridge", "note": "..."}
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum TransposeEntry {
+    Transpose(i32),
+    Annotated {
+        transpose: i32,
+        #[serde(flatten)]
+        annotation: TransposeAnnotation,
+    },
+}
+
 pub unsafe fn process_event(event: Event, app_handle: AppHandle, last_press: Arc<Mutex<Option<Instant>>>) {
     let json: Value = serde_json::from_str(event.payload().unwrap()).expect("failed to parse json");
 
     info!("EVENT FROM FRONTEND: {:?}", json);
     if let Some(new_transposes) = json.get("transposes") {
-        change_transposes_event(new_transposes);
-
-        let json = serde_json::to_string(&json!({"current_index": 0})).unwrap();
-        app_handle.emit_all("frontend_event", Payload { message: json }).unwrap();
+        change_transposes_event(new_transposes, &app_handle);
+        emit_current_index(0, &app_handle);
     }
     else if let Some(new_index) = json.get("selected_index") {
         select_index_event(new_index, app_handle);
@@ -49,10 +65,22 @@ pub unsafe fn process_event(event: Event, app_handle: AppHandle, last_press: Arc
     else if let Some(scroll_value) = json.get("scroll_value") {
         scroll_value_event(scroll_value);
     }
+    else if let Some(metronome) = json.get("metronome") {
+        metronome_event(metronome, app_handle);
+    }
+    else if let Some(sheet_text) = json.get("sheet_text") {
+        sheet_text_event(sheet_text.as_str(), &app_handle);
+    }
+    else if let Some(practice_loop) = json.get("practice_loop") {
+        practice_loop_event(practice_loop, &app_handle);
+    }
 }
 
 unsafe fn pause_event(pause: &Value, app_handle: AppHandle) {
     PAUSED = pause.as_bool().unwrap();
+    if PAUSED {
+        release_sustain(&app_handle);
+    }
 
     let json = serde_json::to_string(&json!({"paused": PAUSED})).unwrap();
     app_handle.emit_all("frontend_event", Payload { message: json });
@@ -70,14 +98,17 @@ unsafe fn scroll_value_event(scroll_value: &Value) {
     SCROLL_VALUE = scroll_value.as_i64().unwrap();
 }
 
-unsafe fn change_transposes_event(new_transposes: &Value) {
-    let mut transposes = TRANSPOSES.lock().unwrap();
-    let mut selected_index = SELECTED_INDEX.lock().unwrap();
+unsafe fn change_transposes_event(new_transposes: &Value, app_handle: &AppHandle) {
+    let entries: Vec<TransposeEntry> = serde_json::from_value(new_transposes.clone()).expect("failed to convert 'transposes' field to vector");
 
-    *selected_index = 0;
-    *transposes = serde_json::from_value(new_transposes.clone()).expect("failed to convert 'transposes' field to vector");
+    let (transposes, annotations) = entries.into_iter()
+        .map(|entry| match entry {
+            TransposeEntry::Transpose(transpose) => (transpose, TransposeAnnotation::default()),
+            TransposeEntry::Annotated { transpose, annotation } => (transpose, annotation),
+        })
+        .unzip();
 
-    CURRENT_TRANSPOSE = transposes[*selected_index];
+    replace_transposes(transposes, annotations, app_handle);
 }
 
 unsafe fn select_index_event(new_index: &Value, app_handle: AppHandle) {
@@ -85,12 +116,15 @@ unsafe fn select_index_event(new_index: &Value, app_handle: AppHandle) {
     let mut transposes = TRANSPOSES.lock().unwrap();
     let mut selected_index = SELECTED_INDEX.lock().unwrap();
 
-    *selected_index = new_index;
-    CURRENT_TRANSPOSE = transposes[new_index];
+    // a click is a step like next/previous, undo goes back to where it was
+    if *selected_index != new_index {
+        history::record(*selected_index, transposes[*selected_index], &app_handle);
+    }
 
-    let json = serde_json::to_string(&json!({"current_index": new_index})).unwrap();
+    *selected_index = new_index;
+    queue_resync(transposes[new_index]);
 
-    app_handle.emit_all("frontend_event", Payload { message: json });
+    emit_current_index(new_index, &app_handle);
 }
 
 unsafe fn key_listen_event(key_listen: &Value) {
@@ -101,13 +135,10 @@ unsafe fn set_keybind_event(keybind: &Value, app_handle: AppHandle, last_press:
     let keycode = keybind.get("keycode").and_then(|k| k.as_u64());
     let bind_name = keybind.get("name").unwrap().as_str().unwrap();
 
-    match bind_name {
-        "pause" => PAUSE_BIND = keycode,
-        "transpose_up" => TRANSPOSE_UP_BIND = keycode,
-        "transpose_down" => TRANSPOSE_DOWN_BIND = keycode,
-        "next_transpose" => NEXT_TRANSPOSE_BIND = keycode,
-        "previous_transpose" => PREVIOUS_TRANSPOSE_BIND = keycode,
-        "scroll_down" => SCROLL_DOWN_BIND = keycode,
-        _ => {}
+    // a held sustain key would never get its release once the bind points elsewhere
+    if bind_name == "sustain" || bind_name == "sustain_key" {
+        release_sustain(&app_handle);
     }
+
+    set_bind(bind_name, keycode);
 }
\ No newline at end of file
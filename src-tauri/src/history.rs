@@ -0,0 +1,113 @@
+use tauri::{AppHandle, Manager};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use log::info;
+use serde_json::json;
+use crate::event_processing::Payload;
+use crate::sheet_positions::emit_current_index;
+use crate::{PAUSED, SELECTED_INDEX, TRANSPOSES, transpose};
+
+// oldest transitions are dropped past this
+const HISTORY_LIMIT: usize = 50;
+
+#[derive(Default)]
+struct TransposeHistory {
+    // (index, transpose) before each next/previous or matrix click, latest last
+    undo: VecDeque<(usize, i32)>,
+    // (index, transpose) before each undo, latest last
+    redo: Vec<(usize, i32)>,
+}
+
+lazy_static! {
+    static ref HISTORY: Mutex<TransposeHistory> = Mutex::new(TransposeHistory::default());
+}
+
+fn emit_history(history: &TransposeHistory, app_handle: &AppHandle) {
+    let json = serde_json::to_string(&json!({
+        "transpose_history": {"undo": history.undo.len(), "redo": history.redo.len()},
+    })).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+}
+
+// indices mean nothing once the transposes are replaced
+pub fn clear() {
+    *HISTORY.lock().unwrap() = TransposeHistory::default();
+}
+
+// called with where next/previous or a matrix click is leaving from, a new step forgets whatever was undone
+pub fn record(index: usize, transpose: i32, app_handle: &AppHandle) {
+    let mut history = HISTORY.lock().unwrap();
+
+    if history.undo.len() == HISTORY_LIMIT {
+        history.undo.pop_front();
+    }
+    history.undo.push_back((index, transpose));
+    history.redo.clear();
+
+    emit_history(&history, app_handle);
+}
+
+// the schedule and autoplay move through the list on their own, redoing would jump back to before them
+pub fn forget_redo(app_handle: &AppHandle) {
+    let mut history = HISTORY.lock().unwrap();
+    if history.redo.is_empty() {
+        return;
+    }
+
+    history.redo.clear();
+    emit_history(&history, app_handle);
+}
+
+// moves to the latest entry of one stack, leaving where we were on the other
+unsafe fn step(undo: bool, app_handle: &AppHandle) {
+    if PAUSED {
+        return;
+    }
+
+    // same order as next/previous, which record while holding the selected index
+    let transposes = TRANSPOSES.lock().unwrap().to_vec();
+    let mut selected_index = SELECTED_INDEX.lock().unwrap();
+    let mut history = HISTORY.lock().unwrap();
+
+    let latest = match undo {
+        true => history.undo.back(),
+        false => history.redo.last(),
+    };
+
+    // only taken off the stack once we know it can be applied
+    let (index, to_transpose) = match latest {
+        Some(&(index, to_transpose)) if index < transposes.len() => (index, to_transpose),
+        _ => return,
+    };
+
+    match undo {
+        true => history.undo.pop_back(),
+        false => history.redo.pop(),
+    };
+
+    // the list's value, the game may still be catching up on queued transposes
+    let from = (*selected_index, transposes[*selected_index]);
+    transpose(to_transpose);
+    *selected_index = index;
+
+    match undo {
+        true => history.redo.push(from),
+        false => history.undo.push_back(from),
+    }
+
+    info!("{} to transpose {} ({:+})", if undo { "Undid" } else { "Redid" }, index, to_transpose);
+
+    emit_current_index(index, app_handle);
+    emit_history(&history, app_handle);
+}
+
+// the undo bind, back to before the last next/previous without stepping through the list
+pub unsafe fn undo_transpose(app_handle: &AppHandle) {
+    step(true, app_handle);
+}
+
+// the redo bind, reapplies the last undo
+pub unsafe fn redo_transpose(app_handle: &AppHandle) {
+    step(false, app_handle);
+}
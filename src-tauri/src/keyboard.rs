@@ -5,9 +5,20 @@ use std::time::{Duration, Instant};
 use rdev::{Event, EventType, simulate, key_from_code, code_from_key, Key};
 use serde_json::{json, Value};
 use serde::{Serialize, Deserialize};
-use crate::{CURRENT_TRANSPOSE, SELECTED_INDEX, transpose, transpose_up, transpose_down, PAUSED, TRANSPOSES, SCROLL_VALUE};
+use crate::{SELECTED_INDEX, transpose, transpose_up, transpose_down, PAUSED, TRANSPOSES};
 use crate::event_processing::Payload;
+use crate::history;
 use crate::audio::{Sound, play_sound};
+use crate::auto_scroll;
+use crate::macros;
+use crate::metronome;
+use crate::practice_loop;
+use crate::schedule;
+use crate::scroll;
+use crate::setlists;
+use crate::sustain;
+use crate::sheet_positions::emit_current_index;
+use crate::virtual_transpose::VIRTUAL_TRANSPOSE;
 use lazy_static::lazy_static;
 use log::{info, error};
 
@@ -21,6 +32,9 @@ enum KeyRepr {
 // for identifying key pressed before setting keybind, value is controlled by frontend
 pub static mut KEY_LISTEN: bool = false;
 
+// minimum time between two next/previous transposes, from settings
+pub static mut TRANSPOSE_DEBOUNCE_MS: u64 = 100;
+
 // keybindings
 pub static mut PAUSE_BIND: Option<u64> = None;
 pub static mut TRANSPOSE_UP_BIND: Option<u64> = None;
@@ -28,6 +42,21 @@ pub static mut TRANSPOSE_DOWN_BIND: Option<u64> = None;
 pub static mut NEXT_TRANSPOSE_BIND: Option<u64> = None;
 pub static mut PREVIOUS_TRANSPOSE_BIND: Option<u64> = None;
 pub static mut SCROLL_DOWN_BIND: Option<u64> = None;
+pub static mut SCROLL_UP_BIND: Option<u64> = None;
+pub static mut AUTO_SCROLL_BIND: Option<u64> = None;
+pub static mut AUTO_SCROLL_FASTER_BIND: Option<u64> = None;
+pub static mut AUTO_SCROLL_SLOWER_BIND: Option<u64> = None;
+pub static mut METRONOME_BIND: Option<u64> = None;
+pub static mut NEXT_SONG_BIND: Option<u64> = None;
+pub static mut PREVIOUS_SONG_BIND: Option<u64> = None;
+pub static mut SCHEDULE_BIND: Option<u64> = None;
+pub static mut LOOP_START_BIND: Option<u64> = None;
+pub static mut LOOP_END_BIND: Option<u64> = None;
+pub static mut UNDO_TRANSPOSE_BIND: Option<u64> = None;
+pub static mut REDO_TRANSPOSE_BIND: Option<u64> = None;
+pub static mut SUSTAIN_BIND: Option<u64> = None;
+// the game's sustain pedal key, held down by the sustain bind
+pub static mut SUSTAIN_KEY_BIND: Option<u64> = None;
 
 // safety for held keys, a keybind action should be only executed on the first keypress
 lazy_static! {
@@ -61,6 +90,123 @@ unsafe fn check_key_held(key: Key) -> bool {
     get_key_is_held_value(&key)
 }
 
+// every bind set_bind knows, keep the two in sync
+pub const BIND_NAMES: &[&str] = &[
+    "pause",
+    "transpose_up",
+    "transpose_down",
+    "next_transpose",
+    "previous_transpose",
+    "scroll_down",
+    "scroll_up",
+    "auto_scroll",
+    "auto_scroll_faster",
+    "auto_scroll_slower",
+    "metronome",
+    "next_song",
+    "previous_song",
+    "schedule",
+    "loop_start",
+    "loop_end",
+    "undo_transpose",
+    "redo_transpose",
+    "sustain",
+    "sustain_key",
+];
+
+pub unsafe fn set_bind(bind_name: &str, keycode: Option<u64>) {
+    match bind_name {
+        "pause" => PAUSE_BIND = keycode,
+        "transpose_up" => TRANSPOSE_UP_BIND = keycode,
+        "transpose_down" => TRANSPOSE_DOWN_BIND = keycode,
+        "next_transpose" => NEXT_TRANSPOSE_BIND = keycode,
+        "previous_transpose" => PREVIOUS_TRANSPOSE_BIND = keycode,
+        "scroll_down" => SCROLL_DOWN_BIND = keycode,
+        "scroll_up" => SCROLL_UP_BIND = keycode,
+        "auto_scroll" => AUTO_SCROLL_BIND = keycode,
+        "auto_scroll_faster" => AUTO_SCROLL_FASTER_BIND = keycode,
+        "auto_scroll_slower" => AUTO_SCROLL_SLOWER_BIND = keycode,
+        "metronome" => METRONOME_BIND = keycode,
+        "next_song" => NEXT_SONG_BIND = keycode,
+        "previous_song" => PREVIOUS_SONG_BIND = keycode,
+        "schedule" => SCHEDULE_BIND = keycode,
+        "loop_start" => LOOP_START_BIND = keycode,
+        "loop_end" => LOOP_END_BIND = keycode,
+        "undo_transpose" => UNDO_TRANSPOSE_BIND = keycode,
+        "redo_transpose" => REDO_TRANSPOSE_BIND = keycode,
+        "sustain" => SUSTAIN_BIND = keycode,
+        "sustain_key" => SUSTAIN_KEY_BIND = keycode,
+        _ => {}
+    }
+}
+
+// binds the app can't transpose without, mirrors "required" in the frontend's default config
+// virtual transposing never presses the game's transpose keys, so those two aren't needed while it's on
+pub unsafe fn required_binds_set() -> bool {
+    PAUSE_BIND.is_some()
+        && (VIRTUAL_TRANSPOSE || (TRANSPOSE_UP_BIND.is_some() && TRANSPOSE_DOWN_BIND.is_some()))
+        && NEXT_TRANSPOSE_BIND.is_some()
+        && PREVIOUS_TRANSPOSE_BIND.is_some()
+}
+
+// keycodes are platform specific, rdev expects u16 on macos and u32 everywhere else
+pub fn key_from_bind(bind: u64) -> Key {
+    #[cfg(target_os = "macos")]
+        let code = bind as u16;
+    #[cfg(not(target_os = "macos"))]
+        let code = bind as u32;
+
+    key_from_code(code)
+}
+
+// keycodes differ per platform, key names (e.g. "Tab", "KeyQ") don't, None for keys rdev can't name
+pub fn keycode_from_key_name(name: &str) -> Option<u64> {
+    let key: Key = serde_json::from_value(Value::String(name.to_string())).ok()?;
+
+    code_from_key(key).map(|code| code as u64)
+}
+
+// runs the action on the first press of an optional bind, true if the key belongs to the bind
+unsafe fn bind_pressed(bind: Option<u64>, key: Key, action: impl FnOnce()) -> bool {
+    let bind_key = match bind.map(key_from_bind) {
+        Some(bind_key) if bind_key == key => bind_key,
+        _ => return false,
+    };
+
+    if !check_key_held(bind_key) {
+        insert_key_is_held_value(bind_key, true);
+        action();
+    }
+
+    true
+}
+
+// true if the key belongs to the bind
+unsafe fn bind_released(bind: Option<u64>, key: Key) -> bool {
+    if let Some(bind_key) = bind.map(key_from_bind) {
+        if bind_key == key {
+            insert_key_is_held_value(bind_key, false);
+            return true;
+        }
+    }
+
+    false
+}
+
+pub unsafe fn set_paused(paused: bool, app_handle: &AppHandle) {
+    PAUSED = paused;
+    if PAUSED {
+        sustain::release_sustain(app_handle);
+        play_sound(Sound::Pause, app_handle.clone());
+    }
+    else {
+        play_sound(Sound::Resume, app_handle.clone());
+    }
+
+    let json = serde_json::to_string(&json!({"paused": PAUSED})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+}
+
 // callback for rdev listener for keyboard events
 pub fn callback(event: Event, app_handle: &AppHandle, last_press: &Arc<Mutex<Option<Instant>>>) {
     match event.event_type {
@@ -97,8 +243,6 @@ pub fn callback(event: Event, app_handle: &AppHandle, last_press: &Arc<Mutex<Opt
                 let next_transpose_key = key_from_code(NEXT_TRANSPOSE_BIND.unwrap() as u32);
             #[cfg(target_os = "windows")]
                 let previous_transpose_key = key_from_code(PREVIOUS_TRANSPOSE_BIND.unwrap() as u32);
-            #[cfg(target_os = "windows")]
-                let scroll_down_key = SCROLL_DOWN_BIND.map(|bind| key_from_code(bind as u32));
 
             #[cfg(target_os = "linux")]
                 let pause_key = key_from_code(PAUSE_BIND.unwrap() as u32);
@@ -106,8 +250,6 @@ pub fn callback(event: Event, app_handle: &AppHandle, last_press: &Arc<Mutex<Opt
                 let next_transpose_key = key_from_code(NEXT_TRANSPOSE_BIND.unwrap() as u32);
             #[cfg(target_os = "linux")]
                 let previous_transpose_key = key_from_code(PREVIOUS_TRANSPOSE_BIND.unwrap() as u32);
-            #[cfg(target_os = "linux")]
-                let scroll_down_key = SCROLL_DOWN_BIND.map(|bind| key_from_code(bind as u32));
 
             #[cfg(target_os = "macos")]
                 let pause_key = key_from_code(PAUSE_BIND.unwrap() as u16);
@@ -115,13 +257,24 @@ pub fn callback(event: Event, app_handle: &AppHandle, last_press: &Arc<Mutex<Opt
                 let next_transpose_key = key_from_code(NEXT_TRANSPOSE_BIND.unwrap() as u16);
             #[cfg(target_os = "macos")]
                 let previous_transpose_key = key_from_code(PREVIOUS_TRANSPOSE_BIND.unwrap() as u16);
-            #[cfg(target_os = "macos")]
-                let scroll_down_key = SCROLL_DOWN_BIND.map(|bind| key_from_code(bind as u16));
 
-            if let Some(scroll_down_key) = scroll_down_key {
-                if key == scroll_down_key {
-                    scroll_bind_event();
-                }
+            if bind_pressed(SCROLL_DOWN_BIND, key, || scroll::start_scrolling(-1))
+                || bind_pressed(SCROLL_UP_BIND, key, || scroll::start_scrolling(1))
+                || bind_pressed(AUTO_SCROLL_BIND, key, || auto_scroll::toggle_auto_scroll(app_handle))
+                || bind_pressed(AUTO_SCROLL_FASTER_BIND, key, || auto_scroll::change_auto_scroll_speed(true, app_handle))
+                || bind_pressed(AUTO_SCROLL_SLOWER_BIND, key, || auto_scroll::change_auto_scroll_speed(false, app_handle))
+                || bind_pressed(METRONOME_BIND, key, || metronome::set_metronome(!metronome::METRONOME_ON, app_handle.clone()))
+                || bind_pressed(NEXT_SONG_BIND, key, || setlists::change_song(1, app_handle))
+                || bind_pressed(PREVIOUS_SONG_BIND, key, || setlists::change_song(-1, app_handle))
+                || bind_pressed(SCHEDULE_BIND, key, || schedule::toggle_schedule(app_handle))
+                || bind_pressed(LOOP_START_BIND, key, || practice_loop::set_loop_start(app_handle))
+                || bind_pressed(LOOP_END_BIND, key, || practice_loop::set_loop_end(app_handle))
+                || bind_pressed(UNDO_TRANSPOSE_BIND, key, || history::undo_transpose(app_handle))
+                || bind_pressed(REDO_TRANSPOSE_BIND, key, || history::redo_transpose(app_handle))
+                || bind_pressed(SUSTAIN_BIND, key, || sustain::toggle_sustain(app_handle))
+                || macros::bound_macro(key).is_some_and(|key_macro| bind_pressed(key_macro.key_code, key, || macros::run_macro(key_macro)))
+            {
+                return;
             }
 
             if !PAUSE_BIND.is_none() && key == pause_key {
@@ -131,25 +284,23 @@ pub fn callback(event: Event, app_handle: &AppHandle, last_press: &Arc<Mutex<Opt
 
                 insert_key_is_held_value(pause_key, true);
 
-                if TRANSPOSE_UP_BIND.is_none()
-                    || TRANSPOSE_DOWN_BIND.is_none()
-                    || NEXT_TRANSPOSE_BIND.is_none()
-                    || PREVIOUS_TRANSPOSE_BIND.is_none()
-                {
+                if !required_binds_set() {
                     // prevent resuming if there are required keybindings still
                     return;
                 }
 
-                PAUSED = !PAUSED;
-                if PAUSED {
-                    play_sound(Sound::Pause, app_handle.clone());
+                if metronome::is_counting_in() {
+                    // pressing pause during the count-in cancels it, we never left the paused state
+                    metronome::cancel_count_in();
+                    return;
                 }
-                else {
-                    play_sound(Sound::Resume, app_handle.clone());
+
+                if PAUSED && metronome::count_in_beats() > 0 {
+                    metronome::count_in_then_resume(app_handle.clone());
+                    return;
                 }
 
-                let json = serde_json::to_string(&json!({"paused": PAUSED})).unwrap();
-                app_handle.emit_all("frontend_event", Payload { message: json });
+                set_paused(!PAUSED, app_handle);
             }
             else if !NEXT_TRANSPOSE_BIND.is_none() && key == next_transpose_key {
                 if check_key_held(next_transpose_key) {
@@ -204,6 +355,27 @@ pub fn callback(event: Event, app_handle: &AppHandle, last_press: &Arc<Mutex<Opt
                 let previous_transpose_key = key_from_code(PREVIOUS_TRANSPOSE_BIND.unwrap() as u16);
 
 
+            if bind_released(SCROLL_DOWN_BIND, key) {
+                scroll::stop_scrolling(-1);
+            }
+            if bind_released(SCROLL_UP_BIND, key) {
+                scroll::stop_scrolling(1);
+            }
+
+            bind_released(AUTO_SCROLL_BIND, key);
+            bind_released(AUTO_SCROLL_FASTER_BIND, key);
+            bind_released(AUTO_SCROLL_SLOWER_BIND, key);
+            bind_released(METRONOME_BIND, key);
+            bind_released(NEXT_SONG_BIND, key);
+            bind_released(PREVIOUS_SONG_BIND, key);
+            bind_released(SCHEDULE_BIND, key);
+            bind_released(LOOP_START_BIND, key);
+            bind_released(LOOP_END_BIND, key);
+            bind_released(UNDO_TRANSPOSE_BIND, key);
+            bind_released(REDO_TRANSPOSE_BIND, key);
+            bind_released(SUSTAIN_BIND, key);
+            bind_released(macros::bound_macro(key).and_then(|key_macro| key_macro.key_code), key);
+
             if !PAUSE_BIND.is_none() && key == pause_key {
                 insert_key_is_held_value(pause_key, false);
             }
@@ -218,29 +390,29 @@ pub fn callback(event: Event, app_handle: &AppHandle, last_press: &Arc<Mutex<Opt
     };
 }
 
-pub unsafe fn send_key(code: u64) {
-    #[cfg(target_os = "windows")]
-        let code = code as u32;
-    #[cfg(target_os = "macos")]
-        let code = code as u16;
-    #[cfg(target_os = "linux")]
-        let code = code as u32;
-
-    match simulate(&EventType::KeyPress(key_from_code(code))) {
+pub fn press_key(code: u64) {
+    match simulate(&EventType::KeyPress(key_from_bind(code))) {
         Ok(()) => (),
         Err(SimulateError) => {
             error!("Failed to send KeyPress event for key: {:?}", SimulateError);
         }
     }
+}
 
-    match simulate(&EventType::KeyRelease(key_from_code(code))) {
+pub fn release_key(code: u64) {
+    match simulate(&EventType::KeyRelease(key_from_bind(code))) {
         Ok(()) => (),
         Err(SimulateError) => {
-            error!("Failed to send KeyPress event for key: {:?}", SimulateError);
+            error!("Failed to send KeyRelease event for key: {:?}", SimulateError);
         }
     }
 }
 
+pub unsafe fn send_key(code: u64) {
+    press_key(code);
+    release_key(code);
+}
+
 // keybinding callback functions
 pub unsafe fn next_transpose_bind_fn(app_handle: AppHandle, last_press: Arc<Mutex<Option<Instant>>>) {
     if PAUSED {
@@ -250,24 +422,26 @@ pub unsafe fn next_transpose_bind_fn(app_handle: AppHandle, last_press: Arc<Mute
     let mut transposes = TRANSPOSES.lock().unwrap().to_vec();
     let mut selected_index = SELECTED_INDEX.lock().unwrap();
     let mut last_press = last_press.lock().unwrap();
-    let next_index = (*selected_index + 1) % transposes.len(); // circular
 
     if let Some(instant) = *last_press {
-        if instant.elapsed() < Duration::from_millis(100) {
+        if instant.elapsed() < Duration::from_millis(TRANSPOSE_DEBOUNCE_MS) {
             // Not enough time has passed since the last key press,
             // so ignore this key press.
             return;
         }
     }
 
+    // circular, or back to the start of a practice loop
+    let next_index = practice_loop::next_index(*selected_index, transposes.len(), &app_handle);
+
+    history::record(*selected_index, transposes[*selected_index], &app_handle);
     transpose(transposes[next_index]);
     play_sound(Sound::Next, app_handle.clone());
 
     *last_press = Some(Instant::now());
     *selected_index = next_index;
 
-    let json = serde_json::to_string(&json!({"current_index": next_index})).unwrap();
-    app_handle.emit_all("frontend_event", Payload { message: json}).unwrap();
+    emit_current_index(next_index, &app_handle);
 }
 
 pub unsafe fn previous_transpose_bind_fn(app_handle: AppHandle, last_press: Arc<Mutex<Option<Instant>>>) {
@@ -282,35 +456,19 @@ pub unsafe fn previous_transpose_bind_fn(app_handle: AppHandle, last_press: Arc<
     let next_index = (*selected_index + transposes.len() - 1) % transposes.len(); // circular
 
     if let Some(instant) = *last_press {
-        if instant.elapsed() < Duration::from_millis(100) {
+        if instant.elapsed() < Duration::from_millis(TRANSPOSE_DEBOUNCE_MS) {
             // Not enough time has passed since the last key press,
             // so ignore this key press.
             return;
         }
     }
 
+    history::record(*selected_index, transposes[*selected_index], &app_handle);
     transpose(transposes[next_index]);
     play_sound(Sound::Previous, app_handle.clone());
 
     *last_press = Some(Instant::now());
     *selected_index = next_index;
 
-    let json = serde_json::to_string(&json!({"current_index": next_index})).unwrap();
-    app_handle.emit_all("frontend_event", Payload { message: json }).unwrap();
-}
-
-unsafe fn scroll_bind_event() {
-    if PAUSED {
-        return;
-    }
-
-    match simulate(&EventType::Wheel {
-        delta_x: 0,
-        delta_y: -SCROLL_VALUE,
-    }) {
-        Ok(()) => (),
-        Err(SimulateError) => {
-            println!("We could not send scroll event");
-        }
-    }
+    emit_current_index(next_index, &app_handle);
 }
\ No newline at end of file
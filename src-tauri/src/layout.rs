@@ -0,0 +1,515 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use serde::{Serialize, Deserialize};
+
+// the 61 keys of virtual piano, lowest first, C2 to C7
+pub const VP_KEYS: &str = "1!2@34$5%6^78*9(0qQwWeErtTyYuiIoOpPasSdDfgGhHjJklLzZxcCvVbBnm";
+pub const VP_LOWEST_PITCH: i32 = 36;
+pub const VP_HIGHEST_PITCH: i32 = VP_LOWEST_PITCH + 60;
+
+// the 88 key layout plays A0 to B1 and C#7 to C8 with ctrl held
+const VP_88_LOW_KEYS: &str = "1234567890qwert";
+const VP_88_HIGH_KEYS: &str = "yuiopasdfghj";
+const VP_88_LOWEST_PITCH: i32 = 21;
+
+// what shift turns the digits into on a us keyboard
+const SHIFTED_DIGITS: &str = ")!@#$%^&*(";
+
+#[derive(Debug, thiserror::Error)]
+pub enum LayoutError {
+    #[error("{0}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Modifier {
+    None,
+    Shift,
+    Ctrl,
+}
+
+// a key as it's pressed, key is always the unshifted character: "Q" is shift + 'q', "!" is shift + '1'
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Keystroke {
+    pub key: char,
+    pub modifier: Modifier,
+}
+
+impl Keystroke {
+    pub fn new(key: char, modifier: Modifier) -> Keystroke {
+        Keystroke { key, modifier }
+    }
+
+    // the keystroke typing a character of VP notation, None for characters a key can't type alone
+    pub fn from_char(c: char) -> Option<Keystroke> {
+        match c {
+            c if c.is_ascii_lowercase() || c.is_ascii_digit() => Some(Keystroke::new(c, Modifier::None)),
+            c if c.is_ascii_uppercase() => Some(Keystroke::new(c.to_ascii_lowercase(), Modifier::Shift)),
+            c => SHIFTED_DIGITS.find(c).map(|digit| Keystroke::new((b'0' + digit as u8) as char, Modifier::Shift)),
+        }
+    }
+
+    // the character VP notation writes for the keystroke, None with ctrl, sheets have no way to write those
+    pub fn to_char(self) -> Option<char> {
+        match (self.modifier, self.key) {
+            (Modifier::None, key) => Some(key),
+            (Modifier::Shift, key) if key.is_ascii_lowercase() => Some(key.to_ascii_uppercase()),
+            (Modifier::Shift, key) => SHIFTED_DIGITS.chars().nth(key.to_digit(10)? as usize),
+            (Modifier::Ctrl, _) => None,
+        }
+    }
+
+    // "q", "Q", "!", "shift+q" or "ctrl+q", case matters only without a modifier
+    pub fn parse(text: &str) -> Option<Keystroke> {
+        let (modifier, key) = match text.split_once('+') {
+            Some((modifier, key)) if !key.is_empty() => (modifier.to_ascii_lowercase(), key),
+            _ => return single_char(text).and_then(Keystroke::from_char),
+        };
+
+        let key = single_char(key)?.to_ascii_lowercase();
+        if !(key.is_ascii_lowercase() || key.is_ascii_digit()) {
+            return None;
+        }
+
+        match modifier.as_str() {
+            "shift" => Some(Keystroke::new(key, Modifier::Shift)),
+            "ctrl" => Some(Keystroke::new(key, Modifier::Ctrl)),
+            _ => None,
+        }
+    }
+}
+
+fn single_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+// a custom layout as it's written in json and settings, keys lowest first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayoutFile {
+    pub name: String,
+    pub lowest_pitch: i32,
+    pub keys: Vec<String>,
+}
+
+// the layout picked in settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum LayoutSetting {
+    #[default]
+    Vp61,
+    Vp88,
+    Custom(LayoutFile),
+}
+
+impl LayoutSetting {
+    pub fn layout(&self) -> Result<Layout, LayoutError> {
+        match self {
+            LayoutSetting::Vp61 => Ok(Layout::vp61()),
+            LayoutSetting::Vp88 => Ok(Layout::vp88()),
+            LayoutSetting::Custom(file) => Layout::from_file(file),
+        }
+    }
+}
+
+// which keystroke plays which midi pitch, one key per semitone from lowest_pitch up
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    pub name: String,
+    pub lowest_pitch: i32,
+    keys: Vec<Keystroke>,
+    pitches: HashMap<Keystroke, i32>,
+}
+
+impl Layout {
+    pub fn new(name: &str, lowest_pitch: i32, keys: Vec<Keystroke>) -> Result<Layout, LayoutError> {
+        if keys.is_empty() {
+            return Err(LayoutError::Invalid("A layout needs at least one key".to_string()));
+        }
+
+        let out_of_range = || LayoutError::Invalid("A layout's pitches must be within midi's 0 to 127".to_string());
+
+        // lowest_pitch comes straight from the settings file, checked first so the top pitch can't overflow
+        if !(0..=127).contains(&lowest_pitch) {
+            return Err(out_of_range());
+        }
+
+        let highest_pitch = i32::try_from(keys.len() - 1).ok().and_then(|span| lowest_pitch.checked_add(span));
+        if !matches!(highest_pitch, Some(highest_pitch) if highest_pitch <= 127) {
+            return Err(out_of_range());
+        }
+
+        let mut pitches = HashMap::new();
+        for (index, keystroke) in keys.iter().enumerate() {
+            if pitches.insert(*keystroke, lowest_pitch + index as i32).is_some() {
+                return Err(LayoutError::Invalid(format!("{:?} plays more than one pitch", keystroke)));
+            }
+        }
+
+        Ok(Layout { name: name.to_string(), lowest_pitch, keys, pitches })
+    }
+
+    // the standard virtual piano layout
+    pub fn vp61() -> Layout {
+        let keys = VP_KEYS.chars().map(|c| Keystroke::from_char(c).unwrap()).collect();
+
+        Layout::new("61 keys", VP_LOWEST_PITCH, keys).unwrap()
+    }
+
+    // 61 keys with ctrl for the rest of a full piano, A0 to C8
+    pub fn vp88() -> Layout {
+        let ctrl_keys = |keys: &str| -> Vec<Keystroke> {
+            keys.chars().map(|key| Keystroke::new(key, Modifier::Ctrl)).collect()
+        };
+
+        let keys = [ctrl_keys(VP_88_LOW_KEYS), Layout::vp61().keys, ctrl_keys(VP_88_HIGH_KEYS)].concat();
+
+        Layout::new("88 keys", VP_88_LOWEST_PITCH, keys).unwrap()
+    }
+
+    // {"name": "...", "lowestPitch": 48, "keys": ["1", "!", "ctrl+q", ...]}
+    pub fn from_file(file: &LayoutFile) -> Result<Layout, LayoutError> {
+        let keys = file.keys.iter()
+            .map(|key| Keystroke::parse(key).ok_or(LayoutError::Invalid(format!("\"{}\" is not a key", key))))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Layout::new(&file.name, file.lowest_pitch, keys)
+    }
+
+    pub fn highest_pitch(&self) -> i32 {
+        self.lowest_pitch + self.keys.len() as i32 - 1
+    }
+
+    pub fn contains(&self, pitch: i32) -> bool {
+        (self.lowest_pitch..=self.highest_pitch()).contains(&pitch)
+    }
+
+    // octaves numbered so middle C (60) is C4
+    pub fn octave_bounds(&self) -> (i32, i32) {
+        (self.lowest_pitch.div_euclid(12) - 1, self.highest_pitch().div_euclid(12) - 1)
+    }
+
+    pub fn keystroke_for_pitch(&self, pitch: i32) -> Option<Keystroke> {
+        match self.contains(pitch) {
+            true => Some(self.keys[(pitch - self.lowest_pitch) as usize]),
+            false => None,
+        }
+    }
+
+    pub fn pitch_for_keystroke(&self, keystroke: Keystroke) -> Option<i32> {
+        self.pitches.get(&keystroke).cloned()
+    }
+
+    pub fn char_for_pitch(&self, pitch: i32) -> Option<char> {
+        self.keystroke_for_pitch(pitch)?.to_char()
+    }
+
+    pub fn pitch_for_char(&self, c: char) -> Option<i32> {
+        self.pitch_for_keystroke(Keystroke::from_char(c)?)
+    }
+
+    // brings a pitch the layout can't reach into range by octaves, the lowest key if a layout under an octave has no match
+    pub fn fold(&self, pitch: i32) -> i32 {
+        let mut pitch = pitch;
+        while pitch < self.lowest_pitch {
+            pitch += 12;
+        }
+        while pitch > self.highest_pitch() {
+            pitch -= 12;
+        }
+
+        pitch.max(self.lowest_pitch)
+    }
+}
+
+lazy_static! {
+    // the 61 key layout sheets are written for, everything uses it unless told otherwise
+    pub static ref VP_61: Layout = Layout::vp61();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_json(json: &str) -> Result<Layout, String> {
+        let file: LayoutFile = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        Layout::from_file(&file).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn vp61_spans_c2_to_c7() {
+        let layout = Layout::vp61();
+
+        assert_eq!(layout.lowest_pitch, 36);
+        assert_eq!(layout.highest_pitch(), 96);
+        assert_eq!(layout.octave_bounds(), (2, 7));
+        assert_eq!(VP_KEYS.chars().count(), 61);
+        assert_eq!(*VP_61, layout);
+    }
+
+    #[test]
+    fn vp61_white_and_black_keys() {
+        let layout = Layout::vp61();
+
+        assert_eq!(layout.char_for_pitch(36), Some('1'));
+        assert_eq!(layout.char_for_pitch(37), Some('!'));
+        assert_eq!(layout.char_for_pitch(60), Some('t'));
+        assert_eq!(layout.char_for_pitch(61), Some('T'));
+        assert_eq!(layout.char_for_pitch(96), Some('m'));
+
+        assert_eq!(layout.keystroke_for_pitch(37), Some(Keystroke::new('1', Modifier::Shift)));
+        assert_eq!(layout.keystroke_for_pitch(61), Some(Keystroke::new('t', Modifier::Shift)));
+        assert_eq!(layout.keystroke_for_pitch(60), Some(Keystroke::new('t', Modifier::None)));
+    }
+
+    #[test]
+    fn vp61_out_of_range() {
+        let layout = Layout::vp61();
+
+        assert_eq!(layout.keystroke_for_pitch(35), None);
+        assert_eq!(layout.keystroke_for_pitch(97), None);
+        assert_eq!(layout.char_for_pitch(-1), None);
+        assert!(!layout.contains(35));
+        assert!(layout.contains(36));
+        assert!(layout.contains(96));
+        assert!(!layout.contains(97));
+    }
+
+    #[test]
+    fn vp61_round_trips_every_pitch() {
+        let layout = Layout::vp61();
+
+        for pitch in 36..=96 {
+            let keystroke = layout.keystroke_for_pitch(pitch).unwrap();
+            assert_eq!(layout.pitch_for_keystroke(keystroke), Some(pitch));
+
+            let c = layout.char_for_pitch(pitch).unwrap();
+            assert_eq!(layout.pitch_for_char(c), Some(pitch));
+        }
+    }
+
+    #[test]
+    fn vp61_keys_not_in_layout() {
+        let layout = Layout::vp61();
+
+        // shift + a and shift + 3 aren't notes
+        assert_eq!(layout.pitch_for_char('A'), None);
+        assert_eq!(layout.pitch_for_char('#'), None);
+        assert_eq!(layout.pitch_for_char(' '), None);
+        assert_eq!(layout.pitch_for_char('['), None);
+        assert_eq!(layout.pitch_for_keystroke(Keystroke::new('q', Modifier::Ctrl)), None);
+    }
+
+    #[test]
+    fn layout_settings() {
+        assert_eq!(LayoutSetting::default().layout().unwrap(), Layout::vp61());
+        assert_eq!(LayoutSetting::Vp88.layout().unwrap(), Layout::vp88());
+
+        let setting: LayoutSetting = serde_json::from_str(r#"{"kind": "vp88"}"#).unwrap();
+        assert_eq!(setting, LayoutSetting::Vp88);
+
+        let setting: LayoutSetting = serde_json::from_str(r#"{"kind": "custom", "name": "x", "lowestPitch": 60, "keys": ["a", "b"]}"#).unwrap();
+        assert_eq!(setting.layout().unwrap().highest_pitch(), 61);
+
+        let setting: LayoutSetting = serde_json::from_str(r#"{"kind": "custom", "name": "x", "lowestPitch": 60, "keys": ["a", "a"]}"#).unwrap();
+        assert!(setting.layout().is_err());
+    }
+
+    #[test]
+    fn custom_layout_pitches_stay_within_midi() {
+        assert!(from_json(r#"{"name": "x", "lowestPitch": 126, "keys": ["a", "b"]}"#).is_ok());
+        assert!(from_json(r#"{"name": "x", "lowestPitch": 127, "keys": ["a", "b"]}"#).is_err());
+        assert!(from_json(r#"{"name": "x", "lowestPitch": -1, "keys": ["a"]}"#).is_err());
+
+        // would overflow working out the top pitch
+        assert!(from_json(r#"{"name": "x", "lowestPitch": 2147483647, "keys": ["a", "b"]}"#).is_err());
+    }
+
+    #[test]
+    fn layout_setting_round_trips_through_json() {
+        let setting = LayoutSetting::Custom(LayoutFile { name: "x".to_string(), lowest_pitch: 48, keys: vec!["ctrl+a".to_string()] });
+        let json = serde_json::to_string(&setting).unwrap();
+
+        assert_eq!(serde_json::from_str::<LayoutSetting>(&json).unwrap(), setting);
+    }
+
+    #[test]
+    fn vp88_spans_a0_to_c8() {
+        let layout = Layout::vp88();
+
+        assert_eq!(layout.lowest_pitch, 21);
+        assert_eq!(layout.highest_pitch(), 108);
+        assert_eq!(layout.octave_bounds(), (0, 8));
+    }
+
+    #[test]
+    fn vp88_uses_ctrl_outside_61_keys() {
+        let layout = Layout::vp88();
+
+        assert_eq!(layout.keystroke_for_pitch(21), Some(Keystroke::new('1', Modifier::Ctrl)));
+        assert_eq!(layout.keystroke_for_pitch(35), Some(Keystroke::new('t', Modifier::Ctrl)));
+        assert_eq!(layout.keystroke_for_pitch(97), Some(Keystroke::new('y', Modifier::Ctrl)));
+        assert_eq!(layout.keystroke_for_pitch(108), Some(Keystroke::new('j', Modifier::Ctrl)));
+
+        // ctrl keys have no character in sheets
+        assert_eq!(layout.char_for_pitch(21), None);
+        assert_eq!(layout.char_for_pitch(108), None);
+    }
+
+    #[test]
+    fn vp88_agrees_with_vp61_in_the_middle() {
+        let vp61 = Layout::vp61();
+        let vp88 = Layout::vp88();
+
+        for pitch in 36..=96 {
+            assert_eq!(vp88.keystroke_for_pitch(pitch), vp61.keystroke_for_pitch(pitch));
+        }
+    }
+
+    #[test]
+    fn vp88_round_trips_every_pitch() {
+        let layout = Layout::vp88();
+
+        for pitch in 21..=108 {
+            let keystroke = layout.keystroke_for_pitch(pitch).unwrap();
+            assert_eq!(layout.pitch_for_keystroke(keystroke), Some(pitch));
+        }
+    }
+
+    #[test]
+    fn keystroke_from_char() {
+        assert_eq!(Keystroke::from_char('q'), Some(Keystroke::new('q', Modifier::None)));
+        assert_eq!(Keystroke::from_char('Q'), Some(Keystroke::new('q', Modifier::Shift)));
+        assert_eq!(Keystroke::from_char('5'), Some(Keystroke::new('5', Modifier::None)));
+        assert_eq!(Keystroke::from_char('%'), Some(Keystroke::new('5', Modifier::Shift)));
+        assert_eq!(Keystroke::from_char(')'), Some(Keystroke::new('0', Modifier::Shift)));
+        assert_eq!(Keystroke::from_char('('), Some(Keystroke::new('9', Modifier::Shift)));
+        assert_eq!(Keystroke::from_char('['), None);
+        assert_eq!(Keystroke::from_char('é'), None);
+    }
+
+    #[test]
+    fn keystroke_to_char() {
+        assert_eq!(Keystroke::new('q', Modifier::None).to_char(), Some('q'));
+        assert_eq!(Keystroke::new('q', Modifier::Shift).to_char(), Some('Q'));
+        assert_eq!(Keystroke::new('1', Modifier::Shift).to_char(), Some('!'));
+        assert_eq!(Keystroke::new('0', Modifier::Shift).to_char(), Some(')'));
+        assert_eq!(Keystroke::new('q', Modifier::Ctrl).to_char(), None);
+    }
+
+    #[test]
+    fn keystroke_char_round_trip() {
+        for c in VP_KEYS.chars().chain(")#".chars()) {
+            assert_eq!(Keystroke::from_char(c).and_then(|keystroke| keystroke.to_char()), Some(c));
+        }
+    }
+
+    #[test]
+    fn keystroke_parse() {
+        assert_eq!(Keystroke::parse("q"), Some(Keystroke::new('q', Modifier::None)));
+        assert_eq!(Keystroke::parse("Q"), Some(Keystroke::new('q', Modifier::Shift)));
+        assert_eq!(Keystroke::parse("!"), Some(Keystroke::new('1', Modifier::Shift)));
+        assert_eq!(Keystroke::parse("shift+q"), Some(Keystroke::new('q', Modifier::Shift)));
+        assert_eq!(Keystroke::parse("Shift+Q"), Some(Keystroke::new('q', Modifier::Shift)));
+        assert_eq!(Keystroke::parse("ctrl+1"), Some(Keystroke::new('1', Modifier::Ctrl)));
+        assert_eq!(Keystroke::parse("CTRL+j"), Some(Keystroke::new('j', Modifier::Ctrl)));
+    }
+
+    #[test]
+    fn keystroke_parse_rejects() {
+        assert_eq!(Keystroke::parse(""), None);
+        assert_eq!(Keystroke::parse("qq"), None);
+        assert_eq!(Keystroke::parse("alt+q"), None);
+        assert_eq!(Keystroke::parse("ctrl+"), None);
+        assert_eq!(Keystroke::parse("ctrl+qq"), None);
+        assert_eq!(Keystroke::parse("ctrl+!"), None);
+        assert_eq!(Keystroke::parse("["), None);
+    }
+
+    #[test]
+    fn keystroke_parse_plus_key() {
+        // "+" on its own isn't a modifier split, and shift + '=' isn't a note key
+        assert_eq!(Keystroke::parse("+"), None);
+    }
+
+    #[test]
+    fn custom_layout_from_json() {
+        let layout = from_json(r#"{"name": "Small", "lowestPitch": 60, "keys": ["a", "A", "s", "ctrl+s", "d"]}"#).unwrap();
+
+        assert_eq!(layout.name, "Small");
+        assert_eq!(layout.highest_pitch(), 64);
+        assert_eq!(layout.octave_bounds(), (4, 4));
+        assert_eq!(layout.char_for_pitch(61), Some('A'));
+        assert_eq!(layout.keystroke_for_pitch(63), Some(Keystroke::new('s', Modifier::Ctrl)));
+        assert_eq!(layout.pitch_for_char('d'), Some(64));
+        assert_eq!(layout.pitch_for_char('q'), None);
+    }
+
+    #[test]
+    fn custom_layout_matching_vp61() {
+        let keys: Vec<String> = VP_KEYS.chars().map(|c| c.to_string()).collect();
+        let json = serde_json::json!({"name": "copy", "lowestPitch": 36, "keys": keys}).to_string();
+        let layout = from_json(&json).unwrap();
+
+        for pitch in 36..=96 {
+            assert_eq!(layout.keystroke_for_pitch(pitch), Layout::vp61().keystroke_for_pitch(pitch));
+        }
+    }
+
+    #[test]
+    fn custom_layout_errors() {
+        let error = |json: &str| from_json(json).unwrap_err();
+
+        assert!(error(r#"{"name": "x", "lowestPitch": 60, "keys": []}"#).contains("at least one key"));
+        assert!(error(r#"{"name": "x", "lowestPitch": 60, "keys": ["a", "b", "a"]}"#).contains("more than one pitch"));
+        assert!(error(r#"{"name": "x", "lowestPitch": 60, "keys": ["a", "shift+a", "A"]}"#).contains("more than one pitch"));
+        assert!(error(r#"{"name": "x", "lowestPitch": 60, "keys": ["a", "alt+b"]}"#).contains("\"alt+b\" is not a key"));
+        assert!(error(r#"{"name": "x", "lowestPitch": -1, "keys": ["a"]}"#).contains("0 to 127"));
+        assert!(error(r#"{"name": "x", "lowestPitch": 127, "keys": ["a", "b"]}"#).contains("0 to 127"));
+        assert!(from_json(r#"{"name": "x", "keys": ["a"]}"#).is_err());
+        assert!(from_json("not json").is_err());
+    }
+
+    #[test]
+    fn custom_layout_at_midi_bounds() {
+        assert!(from_json(r#"{"name": "x", "lowestPitch": 0, "keys": ["a"]}"#).is_ok());
+        assert!(from_json(r#"{"name": "x", "lowestPitch": 126, "keys": ["a", "b"]}"#).is_ok());
+    }
+
+    #[test]
+    fn octave_bounds_of_partial_octaves() {
+        let layout = Layout::new("x", 59, vec![Keystroke::new('a', Modifier::None), Keystroke::new('b', Modifier::None)]).unwrap();
+
+        // B3 and C4
+        assert_eq!(layout.octave_bounds(), (3, 4));
+    }
+
+    #[test]
+    fn fold_into_range() {
+        let layout = Layout::vp61();
+
+        assert_eq!(layout.fold(60), 60);
+        assert_eq!(layout.fold(24), 36);
+        assert_eq!(layout.fold(35), 47);
+        assert_eq!(layout.fold(97), 85);
+        assert_eq!(layout.fold(120), 96);
+        assert_eq!(layout.fold(-5), 43);
+    }
+
+    #[test]
+    fn fold_narrow_layout() {
+        let layout = Layout::new("x", 60, vec![Keystroke::new('a', Modifier::None), Keystroke::new('b', Modifier::None)]).unwrap();
+
+        assert_eq!(layout.fold(72), 60);
+        assert_eq!(layout.fold(49), 61);
+        // nothing in the octave class fits, the lowest key plays
+        assert_eq!(layout.fold(66), 60);
+    }
+}
@@ -0,0 +1,178 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use rdev::{simulate, EventType, Key};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::database::DatabaseError;
+use crate::keyboard::key_from_bind;
+use crate::worker::queue_macro;
+use crate::PAUSED;
+
+// a wait is split into slices this long, so a pause stops the macro without sitting out the whole wait
+const WAIT_POLL: Duration = Duration::from_millis(20);
+const MAX_STEPS: usize = 200;
+const MAX_WAIT_MS: u64 = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MacroStep {
+    // rdev key names, e.g. "KeyR" or "Escape", so macros carry over between platforms
+    Press { key: String },
+    Release { key: String },
+    Wait { ms: u64 },
+    #[serde(rename_all = "camelCase")]
+    Wheel {
+        #[serde(default)]
+        delta_x: i64,
+        #[serde(default)]
+        delta_y: i64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyMacro {
+    pub name: String,
+    // the key running the macro, same {key, keyCode} pair a bind's value has
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub key_code: Option<u64>,
+    pub steps: Vec<MacroStep>,
+}
+
+lazy_static! {
+    // the active profile's macros
+    static ref MACROS: Mutex<Vec<KeyMacro>> = Mutex::new(vec![]);
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+    serde_json::from_value(Value::String(name.to_string())).ok()
+}
+
+// bind_keycodes are the profile's own binds, a key can't run a macro and a bind at once
+pub fn validate_macros(macros: &[KeyMacro], bind_keycodes: &[u64]) -> Result<(), DatabaseError> {
+    let invalid = |message: String| Err(DatabaseError::Invalid(message));
+
+    for (index, key_macro) in macros.iter().enumerate() {
+        if key_macro.name.trim().is_empty() {
+            return invalid("Macros need a name".to_string());
+        }
+
+        if key_macro.steps.len() > MAX_STEPS {
+            return invalid(format!("{} has more than {} steps", key_macro.name, MAX_STEPS));
+        }
+
+        for step in &key_macro.steps {
+            match step {
+                MacroStep::Press { key } | MacroStep::Release { key } if key_from_name(key).is_none() => {
+                    return invalid(format!("{} uses an unknown key {}", key_macro.name, key));
+                }
+                MacroStep::Wait { ms } if *ms > MAX_WAIT_MS => {
+                    return invalid(format!("{} waits longer than {}ms", key_macro.name, MAX_WAIT_MS));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(key_code) = key_macro.key_code {
+            if bind_keycodes.contains(&key_code)
+                || macros[..index].iter().any(|other| other.key_code == Some(key_code))
+            {
+                return invalid(format!("{}'s key is already in use", key_macro.name));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn set_macros(macros: Vec<KeyMacro>) {
+    info!("{} keybind macros", macros.len());
+
+    *MACROS.lock().unwrap() = macros;
+}
+
+// the macro whose key was pressed
+pub fn bound_macro(key: Key) -> Option<KeyMacro> {
+    MACROS.lock().unwrap()
+        .iter()
+        .find(|key_macro| key_macro.key_code.map(key_from_bind) == Some(key))
+        .cloned()
+}
+
+fn send(event_type: &EventType) {
+    if let Err(err) = simulate(event_type) {
+        error!("Failed to send {:?}: {:?}", event_type, err);
+    }
+}
+
+// false if the app was paused while waiting
+unsafe fn wait(duration: Duration) -> bool {
+    let mut waited = Duration::ZERO;
+
+    while waited < duration {
+        if PAUSED {
+            return false;
+        }
+
+        let slice = (duration - waited).min(WAIT_POLL);
+        std::thread::sleep(slice);
+        waited += slice;
+    }
+
+    !PAUSED
+}
+
+// runs on the key worker, a transpose in progress finishes first and none starts until the macro is done
+pub unsafe fn play_macro(key_macro: &KeyMacro) {
+    info!("Running macro {}", key_macro.name);
+
+    // keys the macro pressed and hasn't released yet
+    let mut held: Vec<Key> = vec![];
+
+    for step in &key_macro.steps {
+        if PAUSED {
+            warn!("Macro {} stopped by pause", key_macro.name);
+            break;
+        }
+
+        match step {
+            MacroStep::Press { key } => {
+                if let Some(key) = key_from_name(key) {
+                    send(&EventType::KeyPress(key));
+                    held.push(key);
+                }
+            }
+            MacroStep::Release { key } => {
+                if let Some(key) = key_from_name(key) {
+                    send(&EventType::KeyRelease(key));
+                    held.retain(|held_key| *held_key != key);
+                }
+            }
+            MacroStep::Wait { ms } => {
+                if !wait(Duration::from_millis(*ms)) {
+                    warn!("Macro {} stopped by pause", key_macro.name);
+                    break;
+                }
+            }
+            MacroStep::Wheel { delta_x, delta_y } => send(&EventType::Wheel { delta_x: *delta_x, delta_y: *delta_y }),
+        }
+    }
+
+    // a stopped or sloppy macro never leaves a key down in the game
+    for key in held {
+        send(&EventType::KeyRelease(key));
+    }
+}
+
+// waits and all, a macro would hold up the key listener, so it's queued for the key worker
+pub unsafe fn run_macro(key_macro: KeyMacro) {
+    if PAUSED {
+        return;
+    }
+
+    queue_macro(key_macro);
+}
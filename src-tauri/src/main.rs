@@ -34,11 +34,36 @@ use linux::*;
 mod event_processing;
 mod keyboard;
 mod audio;
+mod metronome;
+mod database;
+mod profiles;
+mod sheets;
+mod setlists;
+mod settings;
+mod bundle;
+mod sheet_parser;
+mod sheet_positions;
+mod midi_import;
+mod planner;
+mod musicxml_import;
+mod transpose_expression;
+mod autoplay;
+mod schedule;
+mod practice_loop;
+mod virtual_transpose;
+mod layout;
+mod sustain;
+mod macros;
+mod scroll;
+mod auto_scroll;
+mod history;
+mod worker;
 
 use crate::keyboard::{TRANSPOSE_DOWN_BIND, TRANSPOSE_UP_BIND, send_key};
+use crate::database::Database;
+use crate::settings::SettingsStore;
 
-use tauri::Manager;
-use tauri_plugin_sql::{Builder, Migration, MigrationKind};
+use tauri::{AppHandle, Manager};
 use tauri_plugin_log::{LogTarget};
 use log::{error, info};
 use std::sync::{Arc, Mutex};
@@ -55,24 +80,82 @@ static mut SCROLL_VALUE: i64 = 0;
 
 // transposition logic
 
+// optional details for a transpose, so the monitor can show "Bridge (+3)"
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TransposeAnnotation {
+    pub label: Option<String>,
+    pub note: Option<String>,
+    // seconds into the song, for the schedule to transpose on its own
+    pub time: Option<f64>,
+}
+
 lazy_static! {
     static ref TRANSPOSES: Mutex<Vec<i32>> = Mutex::new(vec![0]);
     static ref SELECTED_INDEX: Arc<Mutex<usize>> = Arc::new(Mutex::new(0));
+    // TRANSPOSE_ANNOTATIONS[i] belongs to TRANSPOSES[i], both are always the same length
+    static ref TRANSPOSE_ANNOTATIONS: Mutex<Vec<TransposeAnnotation>> = Mutex::new(vec![TransposeAnnotation::default()]);
 }
 
-unsafe fn calculate_next_transpose_difference(next_transpose: i32) -> i32 {
-    if next_transpose == CURRENT_TRANSPOSE {
+// swaps in a new transpose list, starting again from its first transpose
+pub unsafe fn replace_transposes(new_transposes: Vec<i32>, mut annotations: Vec<TransposeAnnotation>, app_handle: &AppHandle) {
+    let mut transposes = TRANSPOSES.lock().unwrap();
+    let mut selected_index = SELECTED_INDEX.lock().unwrap();
+
+    *selected_index = 0;
+    *transposes = if new_transposes.is_empty() { vec![0] } else { new_transposes };
+
+    annotations.resize(transposes.len(), TransposeAnnotation::default());
+    *TRANSPOSE_ANNOTATIONS.lock().unwrap() = annotations;
+    sheet_positions::sync_transposes(&transposes);
+    practice_loop::clear();
+    history::clear();
+    // its times belong to the old list
+    schedule::stop_schedule(app_handle);
+
+    worker::queue_resync(transposes[*selected_index]);
+}
+
+pub fn transpose_annotation(index: usize) -> TransposeAnnotation {
+    TRANSPOSE_ANNOTATIONS.lock().unwrap().get(index).cloned().unwrap_or_default()
+}
+
+// how many transpose up/down presses it takes to go from one transpose to another
+pub fn transpose_difference(from: i32, to: i32) -> i32 {
+    if from == to {
         return 0;
     }
 
-    (CURRENT_TRANSPOSE - next_transpose).abs()
+    (from - to).abs()
+}
+
+unsafe fn calculate_next_transpose_difference(next_transpose: i32) -> i32 {
+    transpose_difference(CURRENT_TRANSPOSE, next_transpose)
+}
+
+// queued on the key worker behind any macro or transpose still sending, returns before the keys are sent
+fn transpose(transpose: i32) {
+    worker::queue_transpose(transpose);
 }
 
-unsafe fn transpose(transpose: i32) {
+// runs on the key worker, CURRENT_TRANSPOSE is where the game is once the keys are sent
+unsafe fn send_transpose(transpose: i32) {
     if transpose == CURRENT_TRANSPOSE {
         return;
     }
 
+    // note keys are remapped as they're played, the game itself stays untransposed
+    if virtual_transpose::VIRTUAL_TRANSPOSE {
+        CURRENT_TRANSPOSE = transpose;
+        return;
+    }
+
+    // only optional while virtual transposing, which may have been turned off since
+    if TRANSPOSE_UP_BIND.is_none() || TRANSPOSE_DOWN_BIND.is_none() {
+        error!("Transpose up/down aren't bound, not transposing");
+        return;
+    }
+
     info!("Beginning transposing...");
     let is_transposing_up = CURRENT_TRANSPOSE < transpose;
     let transpose_amount: i32 = calculate_next_transpose_difference(transpose);
@@ -148,16 +231,6 @@ fn set_window_focusable(window: tauri::Window, focusable: bool) {
 }
 
 fn main() {
-    let db_migrations = vec![
-        // Define your migrations here
-        Migration {
-            version: 1,
-            description: "init_tables",
-            sql: include_str!("./migrations/init_tables.sql"),
-            kind: MigrationKind::Up,
-        }
-    ];
-
     // Set a custom panic hook to log panics
     panic::set_hook(Box::new(|panic_info| {
         // Extract panic message and location
@@ -185,11 +258,38 @@ fn main() {
     // gui
     tauri::Builder::default()
         .device_event_filter(tauri::DeviceEventFilter::Always)
-        .invoke_handler(tauri::generate_handler![set_window_focusable])
+        .invoke_handler(tauri::generate_handler![
+            set_window_focusable,
+            profiles::save_keybind_profile,
+            profiles::load_keybind_profile,
+            profiles::list_keybind_profiles,
+            profiles::delete_keybind_profile,
+            sheets::save_sheet,
+            sheets::get_sheet_by_id,
+            sheets::list_sheets,
+            sheets::delete_sheet,
+            sheets::load_sheet,
+            setlists::save_setlist,
+            setlists::list_setlists,
+            setlists::delete_setlist,
+            setlists::start_setlist,
+            settings::get_settings,
+            settings::patch_settings,
+            bundle::export_profile,
+            bundle::preview_profile_import,
+            bundle::import_profile,
+            sheet_parser::parse_sheet_transposes,
+            sheet_parser::parse_transpose_list,
+            midi_import::import_midi,
+            planner::plan_transposes,
+            musicxml_import::import_musicxml,
+            transpose_expression::parse_transpose_expression,
+            autoplay::start_autoplay,
+            autoplay::stop_autoplay,
+            autoplay::seek_autoplay,
+        ])
         .plugin(
             tauri_plugin_sql::Builder::default()
-                // idk why these migrations won't run, these tables will just have to be added from the frontend for now I guess
-                // .add_migrations("sqlite:multi_transpose.db", db_migrations)
                 .build(),
         )
         .plugin(tauri_plugin_log::Builder::default().targets([
@@ -206,11 +306,31 @@ fn main() {
                     }
                 }
             }
+            tauri::WindowEvent::Focused(focused) => {
+                // focus leaving one window arrives before it reaches the next
+                unsafe { virtual_transpose::APP_FOCUSED = *focused; }
+            }
             _ => {}
         })
         .setup(|app| {
             // app ready
 
+            match SettingsStore::open(&app.handle()) {
+                Ok(store) => {
+                    unsafe { store.get().apply(); }
+                    app.manage(store);
+                }
+                Err(err) => error!("Failed to open settings: {}", err),
+            }
+
+            // migrations run here, before the frontend gets a chance to query anything
+            let conn = database::open(&app.handle());
+            match &conn {
+                Ok(conn) => unsafe { profiles::restore_active_profile(conn, &app.handle()) },
+                Err(err) => error!("Failed to open database: {}", err),
+            }
+            app.manage(Database::new(conn));
+
             // use the tauri app handle for communication with the frontend
             let app_ = Arc::new(Mutex::new(app.handle()));
             let last_press = Arc::new(Mutex::new(None::<Instant>));
@@ -240,6 +360,12 @@ fn main() {
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            // however the app quits, the game would keep the sustain key held after we're gone
+            if let tauri::RunEvent::Exit = event {
+                unsafe { sustain::release_sustain(app_handle); }
+            }
+        });
 }
@@ -0,0 +1,213 @@
+use tauri::{AppHandle, Manager};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use lazy_static::lazy_static;
+use log::{error, info};
+use rodio::{OutputStream, OutputStreamHandle, source::{Source, SineWave}};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::audio::MUTED;
+use crate::event_processing::Payload;
+use crate::keyboard::set_paused;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetronomeConfig {
+    pub bpm: f32,
+    // time signature, bpm is counted in quarter notes
+    pub beats_per_bar: u32,
+    pub beat_unit: u32,
+    pub accent: bool,
+    pub volume: f32,
+    // beats played before resuming through the pause bind, 0 to disable
+    pub count_in: u32,
+}
+
+impl Default for MetronomeConfig {
+    fn default() -> Self {
+        MetronomeConfig {
+            bpm: 120.0,
+            beats_per_bar: 4,
+            beat_unit: 4,
+            accent: true,
+            volume: 0.5,
+            count_in: 0,
+        }
+    }
+}
+
+impl MetronomeConfig {
+    fn beat_interval(&self) -> Duration {
+        let bpm = self.bpm.clamp(20.0, 400.0) as f64;
+        let beat_unit = self.beat_unit.max(1) as f64;
+
+        Duration::from_secs_f64(60.0 / bpm * 4.0 / beat_unit)
+    }
+}
+
+pub static mut METRONOME_ON: bool = false;
+static mut COUNTING_IN: bool = false;
+
+// bumped every time a metronome thread is started, older threads see the change and exit
+static mut METRONOME_GENERATION: u64 = 0;
+// same for count-ins, bumped when one starts or is cancelled, so a cancelled one can't resume after a new one began
+static mut COUNT_IN_GENERATION: u64 = 0;
+
+lazy_static! {
+    pub static ref METRONOME_CONFIG: Mutex<MetronomeConfig> = Mutex::new(MetronomeConfig::default());
+}
+
+const CLICK_DURATION: Duration = Duration::from_millis(40);
+const ACCENT_FREQUENCY: f32 = 1760.0;
+const BEAT_FREQUENCY: f32 = 1320.0;
+
+fn click(stream_handle: &OutputStreamHandle, accent: bool, volume: f32) {
+    let frequency = if accent { ACCENT_FREQUENCY } else { BEAT_FREQUENCY };
+    let source = SineWave::new(frequency)
+        .take_duration(CLICK_DURATION)
+        .amplify(volume);
+
+    if let Err(err) = stream_handle.play_raw(source) {
+        error!("{:?}", err);
+    }
+}
+
+fn open_output_stream() -> Option<(OutputStream, OutputStreamHandle)> {
+    match OutputStream::try_default() {
+        Ok(stream) => Some(stream),
+        Err(err) => {
+            error!("{:?}", err);
+            None
+        }
+    }
+}
+
+// sleeps until the given deadline, scheduling against an absolute time so beats don't drift
+pub fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now {
+        std::thread::sleep(deadline - now);
+    }
+}
+
+pub unsafe fn set_metronome(on: bool, app_handle: AppHandle) {
+    if on == METRONOME_ON {
+        return;
+    }
+
+    METRONOME_ON = on;
+    if on {
+        start_metronome();
+    }
+
+    let json = serde_json::to_string(&json!({"metronome": METRONOME_ON})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+}
+
+unsafe fn start_metronome() {
+    METRONOME_GENERATION += 1;
+    let generation = METRONOME_GENERATION;
+
+    std::thread::spawn(move || {
+        let (_stream, stream_handle) = match open_output_stream() {
+            Some(stream) => stream,
+            None => return,
+        };
+
+        info!("Metronome started");
+
+        let mut beat: u32 = 0;
+        let mut next_beat = Instant::now();
+        while METRONOME_ON && METRONOME_GENERATION == generation {
+            let config = METRONOME_CONFIG.lock().unwrap().clone();
+
+            // keeps counting beats while muted, so unmuting lands back on the right one
+            if !MUTED {
+                click(&stream_handle, config.accent && beat == 0, config.volume);
+            }
+
+            beat = (beat + 1) % config.beats_per_bar.max(1);
+            next_beat += config.beat_interval();
+            sleep_until(next_beat);
+        }
+
+        info!("Metronome stopped");
+    });
+}
+
+pub unsafe fn count_in_beats() -> u32 {
+    METRONOME_CONFIG.lock().unwrap().count_in
+}
+
+pub unsafe fn is_counting_in() -> bool {
+    COUNTING_IN
+}
+
+pub unsafe fn cancel_count_in() {
+    COUNT_IN_GENERATION += 1;
+    COUNTING_IN = false;
+}
+
+// plays the configured count-in, then unpauses
+pub unsafe fn count_in_then_resume(app_handle: AppHandle) {
+    if COUNTING_IN {
+        return;
+    }
+
+    COUNT_IN_GENERATION += 1;
+    COUNTING_IN = true;
+    let generation = COUNT_IN_GENERATION;
+
+    std::thread::spawn(move || {
+        let config = METRONOME_CONFIG.lock().unwrap().clone();
+
+        if let Some((_stream, stream_handle)) = open_output_stream() {
+            let mut next_beat = Instant::now();
+            for beat in 0..config.count_in {
+                if COUNT_IN_GENERATION != generation {
+                    // pause bind pressed again during the count-in
+                    return;
+                }
+
+                // muted still waits out the count-in, so resuming takes as long either way
+                if !MUTED {
+                    click(&stream_handle, config.accent && beat % config.beats_per_bar.max(1) == 0, config.volume);
+                }
+
+                next_beat += config.beat_interval();
+                sleep_until(next_beat);
+            }
+        }
+
+        if COUNT_IN_GENERATION != generation {
+            return;
+        }
+
+        COUNTING_IN = false;
+        set_paused(false, &app_handle);
+    });
+}
+
+pub unsafe fn metronome_event(metronome: &Value, app_handle: AppHandle) {
+    if let Some(on) = metronome.as_bool() {
+        set_metronome(on, app_handle);
+        return;
+    }
+
+    // partial updates, fields not sent keep their current value
+    let mut config = METRONOME_CONFIG.lock().unwrap();
+    let mut merged = serde_json::to_value(&*config).unwrap();
+    if let (Some(merged), Some(fields)) = (merged.as_object_mut(), metronome.as_object()) {
+        for (field, value) in fields {
+            merged.insert(field.clone(), value.clone());
+        }
+    }
+
+    match serde_json::from_value::<MetronomeConfig>(merged) {
+        Ok(new_config) => {
+            // amplify takes anything, past 1.0 clips
+            *config = MetronomeConfig { volume: new_config.volume.clamp(0.0, 1.0), ..new_config };
+        }
+        Err(err) => error!("Invalid metronome config: {}", err),
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use log::info;
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use serde::Serialize;
+use crate::layout::{VP_61, VP_HIGHEST_PITCH, VP_LOWEST_PITCH};
+
+// general midi puts percussion on channel 10, those aren't notes
+const DRUM_CHANNEL: u8 = 9;
+// 120 bpm, what a file without a tempo event plays at
+const DEFAULT_MICROS_PER_BEAT: u32 = 500_000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MidiError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("not a valid midi file: {0}")]
+    Parse(#[from] midly::Error),
+    #[error("{0}")]
+    Unsupported(String),
+    #[error("midi file has no notes")]
+    Empty,
+}
+
+impl Serialize for MidiError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiImport {
+    // one line per bar, with a transpose marker wherever the transpose changes
+    pub text: String,
+    pub transposes: Vec<i32>,
+    // seconds from the start of the file to the first note of each transpose, for the schedule
+    pub times: Vec<f64>,
+    // every chord's midi pitches in order, what the planner takes
+    pub notes: Vec<Vec<i32>>,
+}
+
+struct Note {
+    tick: u64,
+    pitch: i32,
+}
+
+struct MidiEvents {
+    // note onsets from every track on absolute ticks
+    notes: Vec<Note>,
+    // (tick, bar length) for each time signature
+    signatures: Vec<(u64, u64)>,
+    // (tick, microseconds per beat) for each tempo change
+    tempos: Vec<(u64, u32)>,
+}
+
+fn read_notes(smf: &Smf, ticks_per_beat: u64) -> MidiEvents {
+    let mut notes = vec![];
+    let mut signatures = vec![];
+    let mut tempos = vec![];
+
+    for track in &smf.tracks {
+        let mut tick = 0;
+
+        for event in track {
+            tick += event.delta.as_int() as u64;
+
+            match event.kind {
+                TrackEventKind::Midi { channel, message: MidiMessage::NoteOn { key, vel } } => {
+                    // note on with no velocity is how a lot of files write note off
+                    if vel.as_int() > 0 && channel.as_int() != DRUM_CHANNEL {
+                        notes.push(Note { tick, pitch: key.as_int() as i32 });
+                    }
+                }
+                TrackEventKind::Meta(MetaMessage::TimeSignature(numerator, denominator, _, _)) => {
+                    let bar_length = (ticks_per_beat * 4 * numerator as u64) >> denominator.min(6);
+                    if bar_length > 0 {
+                        signatures.push((tick, bar_length));
+                    }
+                }
+                TrackEventKind::Meta(MetaMessage::Tempo(micros_per_beat)) => tempos.push((tick, micros_per_beat.as_int())),
+                _ => {}
+            }
+        }
+    }
+
+    notes.sort_by_key(|note| (note.tick, note.pitch));
+    signatures.sort_by_key(|(tick, _)| *tick);
+    tempos.sort_by_key(|(tick, _)| *tick);
+
+    MidiEvents { notes, signatures, tempos }
+}
+
+// seconds from the start to a tick, going through every tempo change before it
+fn seconds_at(tick: u64, tempos: &[(u64, u32)], ticks_per_beat: u64) -> f64 {
+    let mut seconds = 0.0;
+    let mut last_tick = 0;
+    let mut micros_per_beat = DEFAULT_MICROS_PER_BEAT;
+
+    for &(tempo_tick, tempo) in tempos.iter().take_while(|(tempo_tick, _)| *tempo_tick <= tick) {
+        seconds += (tempo_tick - last_tick) as f64 * micros_per_beat as f64 / ticks_per_beat as f64 / 1_000_000.0;
+        last_tick = tempo_tick;
+        micros_per_beat = tempo;
+    }
+
+    seconds + (tick - last_tick) as f64 * micros_per_beat as f64 / ticks_per_beat as f64 / 1_000_000.0
+}
+
+// bar of every note, a time signature change always starts a new bar
+fn bar_numbers(notes: &[Note], signatures: &[(u64, u64)], ticks_per_beat: u64) -> Vec<usize> {
+    let mut signatures = signatures.iter().peekable();
+    let mut bar = 0;
+    let mut bar_start = 0;
+    let mut bar_length = ticks_per_beat * 4;
+
+    notes.iter()
+        .map(|note| {
+            loop {
+                let next_bar = bar_start + bar_length;
+
+                match signatures.peek() {
+                    Some(&&(tick, length)) if tick <= note.tick && tick <= next_bar => {
+                        if tick > bar_start {
+                            bar += 1;
+                        }
+
+                        bar_start = tick;
+                        bar_length = length;
+                        signatures.next();
+                    }
+                    _ if note.tick >= next_bar => {
+                        bar += 1;
+                        bar_start = next_bar;
+                    }
+                    _ => break,
+                }
+            }
+
+            bar
+        })
+        .collect()
+}
+
+// stays on the previous transpose while it still covers the bar, otherwise moves the least it can
+fn section_transpose(pitches: &[i32], previous: i32) -> i32 {
+    let lowest = pitches.iter().min().cloned().unwrap_or(VP_LOWEST_PITCH);
+    let highest = pitches.iter().max().cloned().unwrap_or(VP_LOWEST_PITCH);
+
+    // a bar wider than 61 keys can't fit, so keep its highest note, usually the melody, and fold the rest
+    let min_transpose = highest - VP_HIGHEST_PITCH;
+    let max_transpose = (lowest - VP_LOWEST_PITCH).max(min_transpose);
+
+    previous.clamp(min_transpose, max_transpose)
+}
+
+// VP keys for notes played together on a transpose, "[tuo]" for chords
+pub fn chord_text(pitches: &[i32], transpose: i32) -> String {
+    let mut keys: Vec<i32> = pitches.iter().map(|pitch| VP_61.fold(pitch - transpose)).collect();
+    keys.sort();
+    keys.dedup();
+
+    let keys: String = keys.into_iter().filter_map(|pitch| VP_61.char_for_pitch(pitch)).collect();
+
+    match keys.chars().count() {
+        1 => keys,
+        _ => format!("[{}]", keys),
+    }
+}
+
+pub fn import(bytes: &[u8]) -> Result<MidiImport, MidiError> {
+    let smf = Smf::parse(bytes)?;
+
+    let ticks_per_beat = match smf.header.timing {
+        Timing::Metrical(ticks_per_beat) => (ticks_per_beat.as_int() as u64).max(1),
+        Timing::Timecode(_, _) => return Err(MidiError::Unsupported("SMPTE timed midi files are not supported".to_string())),
+    };
+
+    let MidiEvents { notes, signatures, tempos } = read_notes(&smf, ticks_per_beat);
+    if notes.is_empty() {
+        return Err(MidiError::Empty);
+    }
+
+    // onsets a 32nd note apart or less are played together
+    let grid = (ticks_per_beat / 8).max(1);
+
+    // bar -> chord onset -> pitches, and the tick of each bar's first note
+    let mut bars: BTreeMap<usize, BTreeMap<u64, Vec<i32>>> = BTreeMap::new();
+    let mut bar_ticks: BTreeMap<usize, u64> = BTreeMap::new();
+    for (note, bar) in notes.iter().zip(bar_numbers(&notes, &signatures, ticks_per_beat)) {
+        let onset = (note.tick + grid / 2) / grid;
+        bars.entry(bar).or_default().entry(onset).or_default().push(note.pitch);
+        bar_ticks.entry(bar).or_insert(note.tick);
+    }
+
+    let mut text = String::new();
+    let mut transposes = vec![];
+    let mut times = vec![];
+    let notes = bars.values().flat_map(|chords| chords.values().cloned()).collect();
+
+    for (bar, chords) in &bars {
+        let pitches: Vec<i32> = chords.values().flatten().cloned().collect();
+        let transpose = section_transpose(&pitches, transposes.last().cloned().unwrap_or(0));
+
+        if transposes.last() != Some(&transpose) {
+            text.push_str(&format!("[Transpose: {:+}]\n", transpose));
+            transposes.push(transpose);
+            times.push(seconds_at(bar_ticks[bar], &tempos, ticks_per_beat));
+        }
+
+        let line: Vec<String> = chords.values().map(|pitches| chord_text(pitches, transpose)).collect();
+        text.push_str(&line.join(" "));
+        text.push('\n');
+    }
+
+    Ok(MidiImport { text, transposes, times, notes })
+}
+
+#[tauri::command]
+pub fn import_midi(path: String) -> Result<MidiImport, MidiError> {
+    let midi_import = import(&std::fs::read(Path::new(&path))?)?;
+    info!("Imported midi {} with {} transposes", path, midi_import.transposes.len());
+
+    Ok(midi_import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_follow_tempo_changes() {
+        // C4 at 120bpm, C4 again at 240bpm, then a note above the 61 keys in the third bar
+        let midi_import = import(include_bytes!("../tests/fixtures/tempo_change.mid")).unwrap();
+
+        assert_eq!(midi_import.transposes, vec![0, 4]);
+        // a bar at 120bpm and a bar at 240bpm
+        assert_eq!(midi_import.times, vec![0.0, 3.0]);
+        assert_eq!(midi_import.notes, vec![vec![60], vec![60], vec![100]]);
+    }
+
+    #[test]
+    fn marker_before_every_transpose_change() {
+        let midi_import = import(include_bytes!("../tests/fixtures/tempo_change.mid")).unwrap();
+
+        assert_eq!(midi_import.text, "[Transpose: +0]\nt\nt\n[Transpose: +4]\nm\n");
+    }
+
+    #[test]
+    fn one_transpose_per_bar_that_needs_it() {
+        // 3/4, a C major chord and a C5, a note below the 61 keys, then a chord that still fits on -6
+        // drums and a note on without velocity are left out
+        let midi_import = import(include_bytes!("../tests/fixtures/sections.mid")).unwrap();
+
+        assert_eq!(midi_import.transposes, vec![0, -6]);
+        assert_eq!(midi_import.times, vec![0.0, 1.5]);
+        assert_eq!(midi_import.text, "[Transpose: +0]\n[tuo] s\n[Transpose: -6]\n1\n[IP]\n");
+        assert_eq!(midi_import.notes, vec![vec![60, 64, 67], vec![72], vec![30], vec![60, 64]]);
+    }
+
+    #[test]
+    fn drums_alone_are_empty() {
+        let result = import(include_bytes!("../tests/fixtures/drums_only.mid"));
+
+        assert!(matches!(result, Err(MidiError::Empty)));
+    }
+
+    #[test]
+    fn seconds_at_adds_up_each_tempo() {
+        let tempos = [(0, 500_000), (960, 1_000_000)];
+
+        assert_eq!(seconds_at(480, &tempos, 480), 0.5);
+        assert_eq!(seconds_at(1440, &tempos, 480), 2.0);
+        // before the first tempo event, 120bpm
+        assert_eq!(seconds_at(480, &[(960, 250_000)], 480), 0.5);
+    }
+}
@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS Sheet (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title TEXT NOT NULL,
+    artist TEXT,
+    content TEXT,
+    imagePath TEXT,
+    transposes TEXT NOT NULL DEFAULT '[]',
+    createdAt INTEGER NOT NULL,
+    lastPlayedAt INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS SheetTag (
+    sheetId INTEGER NOT NULL REFERENCES Sheet(id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (sheetId, tag)
+);
+
+CREATE INDEX IF NOT EXISTS SheetTagByTag ON SheetTag (tag);
\ No newline at end of file
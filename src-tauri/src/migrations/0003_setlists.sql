@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS Setlist (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    createdAt INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS SetlistSheet (
+    setlistId INTEGER NOT NULL REFERENCES Setlist(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    sheetId INTEGER NOT NULL REFERENCES Sheet(id) ON DELETE CASCADE,
+    PRIMARY KEY (setlistId, position)
+);
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE Sheet ADD COLUMN transposeAnnotations TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use log::info;
+use roxmltree::{Document, Node, ParsingOptions};
+use serde::Serialize;
+use tauri::State;
+use crate::layout::{VP_HIGHEST_PITCH, VP_LOWEST_PITCH};
+use crate::midi_import::chord_text;
+use crate::planner::{plan, PlanError, PlanObjective, PlanOptions, TransposePlan};
+use crate::settings::SettingsStore;
+
+// note onsets are kept in this many ticks per quarter note, whatever divisions each part uses
+const TICKS_PER_QUARTER: u64 = 960;
+// the score inside a compressed .mxl is named by this file
+const MXL_CONTAINER: &str = "META-INF/container.xml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum MusicXmlError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[error("not a valid musicxml file: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error(transparent)]
+    Plan(#[from] PlanError),
+    #[error("{0}")]
+    Unsupported(String),
+    #[error("musicxml file has no notes")]
+    Empty,
+}
+
+impl Serialize for MusicXmlError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicXmlImport {
+    // one line per measure, each transpose marker names the measure it starts at
+    pub text: String,
+    pub transposes: Vec<i32>,
+    // segments index into measures
+    pub plan: TransposePlan,
+    pub measures: Vec<String>,
+}
+
+struct Measure {
+    number: String,
+    // onset in ticks from the start of the measure -> pitches
+    chords: BTreeMap<u64, Vec<i32>>,
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).and_then(|child| child.text()).map(|text| text.trim())
+}
+
+fn child_number(node: Node, name: &str) -> Option<f64> {
+    child_text(node, name).and_then(|text| text.parse().ok())
+}
+
+fn midi_pitch(pitch: Node) -> Option<i32> {
+    let step = match child_text(pitch, "step")? {
+        "C" => 0,
+        "D" => 2,
+        "E" => 4,
+        "F" => 5,
+        "G" => 7,
+        "A" => 9,
+        "B" => 11,
+        _ => return None,
+    };
+    let alter = child_number(pitch, "alter").unwrap_or(0.0).round() as i32;
+    let octave = child_number(pitch, "octave")? as i32;
+
+    Some((octave + 1) * 12 + step + alter)
+}
+
+// the compressed format is a zip with the score somewhere inside, container.xml says where
+fn read_mxl(reader: impl Read + Seek) -> Result<String, MusicXmlError> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+
+    let mut container = String::new();
+    let root_file = match archive.by_name(MXL_CONTAINER) {
+        Ok(mut file) => {
+            file.read_to_string(&mut container)?;
+            let document = Document::parse(&container)?;
+
+            document.descendants()
+                .find(|node| node.has_tag_name("rootfile"))
+                .and_then(|node| node.attribute("full-path"))
+                .map(|full_path| full_path.to_string())
+        }
+        Err(_) => None,
+    };
+
+    let root_file = match root_file {
+        Some(root_file) => root_file,
+        None => archive.file_names()
+            .find(|name| !name.starts_with("META-INF") && (name.ends_with(".xml") || name.ends_with(".musicxml")))
+            .map(|name| name.to_string())
+            .ok_or(MusicXmlError::Unsupported("compressed musicxml has no score".to_string()))?,
+    };
+
+    let mut xml = String::new();
+    archive.by_name(&root_file)?.read_to_string(&mut xml)?;
+
+    Ok(xml)
+}
+
+// measures of every part laid over each other, measure i of one part plays with measure i of the rest
+fn read_measures(document: &Document) -> Result<Vec<Measure>, MusicXmlError> {
+    let score = document.root_element();
+    if !score.has_tag_name("score-partwise") {
+        return Err(MusicXmlError::Unsupported(format!("<{}> scores are not supported, export as partwise", score.tag_name().name())));
+    }
+
+    let mut measures: Vec<Measure> = vec![];
+
+    for part in score.children().filter(|node| node.has_tag_name("part")) {
+        let mut divisions = 1.0;
+
+        for (index, measure) in part.children().filter(|node| node.has_tag_name("measure")).enumerate() {
+            if measures.len() <= index {
+                let number = measure.attribute("number").unwrap_or_default().to_string();
+                measures.push(Measure { number, chords: BTreeMap::new() });
+            }
+
+            // position in divisions from the start of the measure, and where the last note started for <chord/>
+            let mut position: f64 = 0.0;
+            let mut last_onset: f64 = 0.0;
+
+            for element in measure.children().filter(|node| node.is_element()) {
+                match element.tag_name().name() {
+                    "attributes" => {
+                        if let Some(new_divisions) = child_number(element, "divisions").filter(|divisions| *divisions > 0.0) {
+                            divisions = new_divisions;
+                        }
+                    }
+                    "backup" => position = (position - child_number(element, "duration").unwrap_or(0.0)).max(0.0),
+                    "forward" => position += child_number(element, "duration").unwrap_or(0.0),
+                    "note" => {
+                        let is_chord = child(element, "chord").is_some();
+                        let onset = if is_chord { last_onset } else { position };
+
+                        // grace notes take no time, chord notes share the time of the note before
+                        if !is_chord && child(element, "grace").is_none() {
+                            position += child_number(element, "duration").unwrap_or(0.0);
+                        }
+                        last_onset = onset;
+
+                        // a tied note carries on the one before it, it isn't played again
+                        let is_tie_stop = element.children()
+                            .any(|node| node.has_tag_name("tie") && node.attribute("type") == Some("stop"));
+
+                        if let (Some(pitch), false) = (child(element, "pitch").and_then(midi_pitch), is_tie_stop) {
+                            let tick = (onset / divisions * TICKS_PER_QUARTER as f64).round() as u64;
+                            measures[index].chords.entry(tick).or_default().push(pitch);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok(measures)
+}
+
+// the planner needs every measure to fit on one transpose, notes too far below the highest are raised by octaves,
+// same as the sheet text will fold them
+fn measure_range(measure: &Measure) -> Vec<i32> {
+    let pitches: Vec<i32> = measure.chords.values().flatten().cloned().collect();
+    let highest = match pitches.iter().max() {
+        Some(highest) => *highest,
+        None => return vec![],
+    };
+    let lowest_allowed = highest - (VP_HIGHEST_PITCH - VP_LOWEST_PITCH);
+
+    pitches.into_iter()
+        .map(|mut pitch| {
+            while pitch < lowest_allowed {
+                pitch += 12;
+            }
+            pitch
+        })
+        .collect()
+}
+
+pub fn import(xml: &str, min_transpose: i32, max_transpose: i32) -> Result<MusicXmlImport, MusicXmlError> {
+    // musicxml files declare a doctype, nothing is fetched for it
+    let document = Document::parse_with_options(xml, ParsingOptions { allow_dtd: true, ..ParsingOptions::default() })?;
+    let measures = read_measures(&document)?;
+
+    if measures.iter().all(|measure| measure.chords.is_empty()) {
+        return Err(MusicXmlError::Empty);
+    }
+
+    // transposes only change on measure lines, so each measure is planned as one chord
+    let plan = plan(&measures.iter().map(measure_range).collect::<Vec<_>>(), &PlanOptions {
+        objective: PlanObjective::Changes,
+        min_transpose,
+        max_transpose,
+        min_segment_length: 1,
+        start_transpose: 0,
+    })?;
+
+    let mut text = String::new();
+    for segment in &plan.segments {
+        text.push_str(&format!("[Transpose: {:+}, measure {}]\n", segment.transpose, measures[segment.start].number));
+
+        for measure in &measures[segment.start..segment.end] {
+            let line: Vec<String> = measure.chords.values().map(|pitches| chord_text(pitches, segment.transpose)).collect();
+            text.push_str(&line.join(" "));
+            text.push('\n');
+        }
+    }
+
+    Ok(MusicXmlImport {
+        text,
+        transposes: plan.transposes.clone(),
+        plan,
+        measures: measures.into_iter().map(|measure| measure.number).collect(),
+    })
+}
+
+#[tauri::command]
+pub fn import_musicxml(settings: State<SettingsStore>, path: String) -> Result<MusicXmlImport, MusicXmlError> {
+    let path = Path::new(&path);
+    let xml = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) if extension.eq_ignore_ascii_case("mxl") => read_mxl(File::open(path)?)?,
+        _ => std::fs::read_to_string(path)?,
+    };
+
+    let range = settings.get().range;
+    let musicxml_import = import(&xml, range.min_transpose, range.max_transpose)?;
+    info!("Imported musicxml {} with {} transposes", path.display(), musicxml_import.transposes.len());
+
+    Ok(musicxml_import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // one part, divisions of 1 so durations are in quarter notes
+    fn score(measures: &str) -> String {
+        format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN" "http://www.musicxml.org/dtds/partwise.dtd">
+<score-partwise version="4.0">
+  <part-list><score-part id="P1"/></part-list>
+  <part id="P1">{}</part>
+</score-partwise>"#, measures)
+    }
+
+    fn note(step: &str, octave: i32, duration: u32, extra: &str) -> String {
+        format!("<note>{}<pitch><step>{}</step><octave>{}</octave></pitch><duration>{}</duration></note>", extra, step, octave, duration)
+    }
+
+    // onset in quarter notes -> pitches, for each measure
+    fn chords(xml: &str) -> Vec<Vec<(u64, Vec<i32>)>> {
+        let document = Document::parse_with_options(xml, ParsingOptions { allow_dtd: true, ..ParsingOptions::default() }).unwrap();
+
+        read_measures(&document).unwrap().into_iter()
+            .map(|measure| measure.chords.into_iter().map(|(tick, pitches)| (tick / TICKS_PER_QUARTER, pitches)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn chord_notes_share_the_onset_before() {
+        let xml = score(&format!(
+            r#"<measure number="1"><attributes><divisions>1</divisions></attributes>{}{}{}</measure>"#,
+            note("C", 4, 1, ""), note("E", 4, 1, "<chord/>"), note("G", 4, 1, ""),
+        ));
+
+        assert_eq!(chords(&xml), vec![vec![(0, vec![60, 64]), (1, vec![67])]]);
+    }
+
+    #[test]
+    fn backup_and_forward_move_the_position() {
+        // a second voice backs up to the start of the measure, forward skips a beat with no rest written
+        let xml = score(&format!(
+            r#"<measure number="1"><attributes><divisions>1</divisions></attributes>{}{}<backup><duration>2</duration></backup>{}</measure>
+            <measure number="2"><forward><duration>1</duration></forward>{}</measure>"#,
+            note("C", 4, 1, ""), note("D", 4, 1, ""), note("C", 3, 2, ""), note("E", 4, 1, ""),
+        ));
+
+        assert_eq!(chords(&xml), vec![vec![(0, vec![60, 48]), (1, vec![62])], vec![(1, vec![64])]]);
+    }
+
+    #[test]
+    fn tie_stops_and_grace_notes() {
+        // the tied C carries over the bar line without being played again, the grace note takes no time
+        let xml = score(&format!(
+            r#"<measure number="1"><attributes><divisions>1</divisions></attributes>{}{}</measure>
+            <measure number="2">{}{}</measure>"#,
+            r#"<note><grace/><pitch><step>A</step><octave>4</octave></pitch></note>"#,
+            note("C", 4, 4, "").replace("</duration>", r#"</duration><tie type="start"/>"#),
+            note("C", 4, 1, "").replace("</duration>", r#"</duration><tie type="stop"/>"#),
+            note("D", 4, 1, ""),
+        ));
+
+        assert_eq!(chords(&xml), vec![vec![(0, vec![69, 60])], vec![(1, vec![62])]]);
+    }
+
+    #[test]
+    fn segments_start_at_measure_numbers() {
+        // a pickup numbered 0, then an E7 above the 61 keys
+        let xml = score(&format!(
+            r#"<measure number="0"><attributes><divisions>1</divisions></attributes>{}</measure>
+            <measure number="1">{}</measure>"#,
+            note("C", 4, 1, ""), note("E", 7, 4, ""),
+        ));
+        let musicxml_import = import(&xml, -50, 50).unwrap();
+
+        assert_eq!(musicxml_import.transposes, vec![0, 4]);
+        assert_eq!(musicxml_import.measures, vec!["0", "1"]);
+        assert_eq!(musicxml_import.plan.segments.iter().map(|segment| segment.start).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(musicxml_import.text, "[Transpose: +0, measure 0]\nt\n[Transpose: +4, measure 1]\nm\n");
+    }
+
+    #[test]
+    fn mxl_score_is_found_through_the_container() {
+        // the archive also has another .xml before the score, only container.xml says which is which
+        let xml = read_mxl(Cursor::new(include_bytes!("../tests/fixtures/single_note.mxl"))).unwrap();
+
+        assert_eq!(import(&xml, -50, 50).unwrap().text, "[Transpose: +0, measure 1]\nt\n");
+    }
+
+    #[test]
+    fn unsupported_and_empty_scores() {
+        assert!(matches!(import("<score-timewise/>", -50, 50), Err(MusicXmlError::Unsupported(_))));
+
+        let rests = score(r#"<measure number="1"><note><rest/><duration>4</duration></note></measure>"#);
+        assert!(matches!(import(&rests, -50, 50), Err(MusicXmlError::Empty)));
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::VecDeque;
+use serde::{Serialize, Deserialize};
+use tauri::State;
+use crate::layout::VP_61;
+use crate::settings::SettingsStore;
+use crate::transpose_difference;
+
+// weighs the objective above the tie breaker, no plan gets near this many keypresses or changes
+const PRIMARY_WEIGHT: i64 = 1 << 32;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PlanError {
+    #[error("note {0} can't be played with any transpose in range")]
+    Unplayable(usize),
+    #[error("no plan keeps every transpose for at least {0} notes")]
+    SegmentsTooShort(usize),
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl Serialize for PlanError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanObjective {
+    // fewest times the transpose changes, keypresses break ties
+    Changes,
+    // fewest transpose up/down presses overall, changes break ties
+    Keypresses,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Segment {
+    // note indices, end exclusive
+    pub start: usize,
+    pub end: usize,
+    pub transpose: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransposePlan {
+    pub transposes: Vec<i32>,
+    pub segments: Vec<Segment>,
+    pub changes: usize,
+    pub keypresses: i32,
+}
+
+pub struct PlanOptions {
+    pub objective: PlanObjective,
+    pub min_transpose: i32,
+    pub max_transpose: i32,
+    pub min_segment_length: usize,
+    // the game's transpose before the first segment
+    pub start_transpose: i32,
+}
+
+fn is_playable(chord: &[i32], transpose: i32) -> bool {
+    chord.iter().all(|pitch| VP_61.contains(pitch - transpose))
+}
+
+fn change_cost(objective: PlanObjective, from: i32, to: i32) -> i64 {
+    let keypresses = transpose_difference(from, to) as i64;
+    let changes = (from != to) as i64;
+
+    match objective {
+        PlanObjective::Changes => changes * PRIMARY_WEIGHT + keypresses,
+        PlanObjective::Keypresses => keypresses * PRIMARY_WEIGHT + changes,
+    }
+}
+
+// each note is a chord of midi pitches played together, an empty chord is a rest
+// plans[i][t] is the cheapest way to play the first i notes with a segment on transpose t ending at i,
+// a segment may follow one on the same transpose, those are merged afterwards
+pub fn plan(notes: &[Vec<i32>], options: &PlanOptions) -> Result<TransposePlan, PlanError> {
+    if options.min_transpose > options.max_transpose {
+        return Err(PlanError::Invalid("Transpose range must have its lowest first".to_string()));
+    }
+
+    if notes.is_empty() {
+        return Ok(TransposePlan { transposes: vec![], segments: vec![], changes: 0, keypresses: 0 });
+    }
+
+    if let Some(index) = notes.iter().position(|chord| {
+        !(options.min_transpose..=options.max_transpose).any(|transpose| is_playable(chord, transpose))
+    }) {
+        return Err(PlanError::Unplayable(index));
+    }
+
+    let transposes: Vec<i32> = (options.min_transpose..=options.max_transpose).collect();
+    let min_segment_length = options.min_segment_length.clamp(1, notes.len());
+    let note_count = notes.len();
+
+    // cost, and the (segment start, previous transpose index) it came from
+    let mut plans: Vec<Vec<Option<(i64, usize, usize)>>> = vec![vec![None; transposes.len()]; note_count + 1];
+    // cheapest cost of starting a segment on transpose t at note i, and the previous transpose index
+    let mut starts: Vec<Vec<Option<(i64, usize)>>> = vec![vec![None; transposes.len()]; note_count + 1];
+
+    let start_costs = |plans: &Vec<Option<(i64, usize, usize)>>, to: i32| -> Option<(i64, usize)> {
+        plans.iter()
+            .enumerate()
+            .filter_map(|(from_index, plan)| {
+                plan.map(|(cost, _, _)| (cost + change_cost(options.objective, transposes[from_index], to), from_index))
+            })
+            .min()
+    };
+
+    for (index, &transpose) in transposes.iter().enumerate() {
+        // usize::MAX marks the game's own transpose before any segment
+        starts[0][index] = Some((change_cost(options.objective, options.start_transpose, transpose), usize::MAX));
+    }
+
+    // per transpose, segment starts that can still reach the current note, cheapest at the front
+    let mut windows: Vec<VecDeque<usize>> = vec![VecDeque::new(); transposes.len()];
+    let mut playable_since = vec![0; transposes.len()];
+
+    for end in 1..=note_count {
+        if end > 1 {
+            for (index, &transpose) in transposes.iter().enumerate() {
+                starts[end - 1][index] = start_costs(&plans[end - 1], transpose);
+            }
+        }
+
+        for (index, &transpose) in transposes.iter().enumerate() {
+            let window = &mut windows[index];
+
+            if !is_playable(&notes[end - 1], transpose) {
+                playable_since[index] = end;
+                window.clear();
+                continue;
+            }
+
+            // the segment [start, end) is long enough once start <= end - min_segment_length
+            if end >= min_segment_length {
+                let start = end - min_segment_length;
+
+                if start >= playable_since[index] {
+                    if let Some((cost, _)) = starts[start][index] {
+                        while window.back().map_or(false, |&back| starts[back][index].unwrap().0 >= cost) {
+                            window.pop_back();
+                        }
+
+                        window.push_back(start);
+                    }
+                }
+            }
+
+            while window.front().map_or(false, |&front| front < playable_since[index]) {
+                window.pop_front();
+            }
+
+            plans[end][index] = window.front().map(|&start| {
+                let (cost, previous) = starts[start][index].unwrap();
+                (cost, start, previous)
+            });
+        }
+    }
+
+    let (mut index, mut end) = plans[note_count].iter()
+        .enumerate()
+        .filter_map(|(index, plan)| plan.map(|(cost, _, _)| (cost, index)))
+        .min()
+        .map(|(_, index)| (index, note_count))
+        .ok_or(PlanError::SegmentsTooShort(min_segment_length))?;
+
+    let mut segments: Vec<Segment> = vec![];
+    while end > 0 {
+        let (_, start, previous) = plans[end][index].unwrap();
+
+        match segments.last_mut() {
+            Some(segment) if segment.transpose == transposes[index] => segment.start = start,
+            _ => segments.push(Segment { start, end, transpose: transposes[index] }),
+        }
+
+        index = previous;
+        end = start;
+    }
+    segments.reverse();
+
+    let transposes: Vec<i32> = segments.iter().map(|segment| segment.transpose).collect();
+    let (_, changes, keypresses) = transposes.iter().fold(
+        (options.start_transpose, 0, 0),
+        |(from, changes, keypresses), &to| (to, changes + (from != to) as usize, keypresses + transpose_difference(from, to)),
+    );
+
+    Ok(TransposePlan { transposes, segments, changes, keypresses })
+}
+
+#[tauri::command]
+pub fn plan_transposes(
+    settings: State<SettingsStore>,
+    notes: Vec<Vec<i32>>,
+    objective: PlanObjective,
+    min_segment_length: Option<usize>,
+    start_transpose: Option<i32>,
+) -> Result<TransposePlan, PlanError> {
+    let range = settings.get().range;
+
+    plan(&notes, &PlanOptions {
+        objective,
+        min_transpose: range.min_transpose,
+        max_transpose: range.max_transpose,
+        min_segment_length: min_segment_length.unwrap_or(1),
+        start_transpose: start_transpose.unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(objective: PlanObjective, min_segment_length: usize) -> PlanOptions {
+        PlanOptions { objective, min_transpose: -50, max_transpose: 50, min_segment_length, start_transpose: 0 }
+    }
+
+    fn cost(plan: &TransposePlan, options: &PlanOptions) -> i64 {
+        plan.transposes.iter()
+            .fold((options.start_transpose, 0), |(from, cost), &to| (to, cost + change_cost(options.objective, from, to)))
+            .1
+    }
+
+    // cheapest cost over every transpose for every note, None when nothing keeps each run long enough
+    fn brute_force(notes: &[Vec<i32>], options: &PlanOptions) -> Option<i64> {
+        let min_segment_length = options.min_segment_length.clamp(1, notes.len());
+
+        fn search(notes: &[Vec<i32>], options: &PlanOptions, min_segment_length: usize, from: i32, run: usize, cost: i64) -> Option<i64> {
+            let note = match notes.first() {
+                Some(note) => note,
+                None => return (run >= min_segment_length).then_some(cost),
+            };
+
+            (options.min_transpose..=options.max_transpose)
+                .filter(|&transpose| is_playable(note, transpose))
+                .filter_map(|transpose| match transpose == from && run > 0 {
+                    true => search(&notes[1..], options, min_segment_length, transpose, run + 1, cost),
+                    false if run == 0 || run >= min_segment_length => {
+                        let cost = cost + change_cost(options.objective, from, transpose);
+                        search(&notes[1..], options, min_segment_length, transpose, 1, cost)
+                    }
+                    false => None,
+                })
+                .min()
+        }
+
+        search(notes, options, min_segment_length, options.start_transpose, 0, 0)
+    }
+
+    // xorshift, the same notes every run
+    fn random_notes(seed: &mut u64, count: usize) -> Vec<Vec<i32>> {
+        const PITCHES: [i32; 8] = [30, 33, 34, 60, 94, 97, 99, 102];
+
+        let mut next = || {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 7;
+            *seed ^= *seed << 17;
+            *seed
+        };
+
+        (0..count).map(|_| (0..next() % 3).map(|_| PITCHES[(next() % 8) as usize]).collect()).collect()
+    }
+
+    #[test]
+    fn optimal_on_small_inputs() {
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+
+        for case in 0..300 {
+            let notes = random_notes(&mut seed, 1 + case % 5);
+            let objective = if case % 2 == 0 { PlanObjective::Changes } else { PlanObjective::Keypresses };
+            let options = PlanOptions { min_transpose: -4, max_transpose: 4, ..options(objective, 1 + case % 3) };
+
+            match (plan(&notes, &options), brute_force(&notes, &options)) {
+                (Ok(plan), Some(best)) => assert_eq!(cost(&plan, &options), best, "{:?}", notes),
+                (Err(PlanError::Unplayable(_) | PlanError::SegmentsTooShort(_)), None) => {}
+                (result, best) => panic!("{:?}: planned {:?}, brute force {:?}", notes, result, best),
+            }
+        }
+    }
+
+    #[test]
+    fn segments_cover_every_note_once() {
+        // C1 needs -6 or lower, C4 fits anywhere near 0, E7 needs +4 or higher
+        let notes = vec![vec![24], vec![60], vec![60], vec![100]];
+        let plan = plan(&notes, &options(PlanObjective::Keypresses, 1)).unwrap();
+
+        assert_eq!(plan.segments, vec![
+            Segment { start: 0, end: 3, transpose: -12 },
+            Segment { start: 3, end: 4, transpose: 4 },
+        ]);
+        assert_eq!(plan.changes, 2);
+        assert_eq!(plan.keypresses, 28);
+    }
+
+    #[test]
+    fn short_segments_move_the_change_earlier() {
+        let notes = vec![vec![24], vec![60], vec![60], vec![100]];
+        let plan = plan(&notes, &options(PlanObjective::Keypresses, 2)).unwrap();
+
+        assert_eq!(plan.segments, vec![
+            Segment { start: 0, end: 2, transpose: -12 },
+            Segment { start: 2, end: 4, transpose: 4 },
+        ]);
+    }
+
+    #[test]
+    fn segment_length_longer_than_the_notes_is_one_segment() {
+        let plan = plan(&[vec![60], vec![100]], &options(PlanObjective::Changes, 10)).unwrap();
+
+        assert_eq!(plan.segments, vec![Segment { start: 0, end: 2, transpose: 4 }]);
+    }
+
+    #[test]
+    fn no_segment_can_span_an_unplayable_note() {
+        let result = plan(&[vec![24], vec![100]], &options(PlanObjective::Changes, 2));
+
+        assert!(matches!(result, Err(PlanError::SegmentsTooShort(2))));
+    }
+
+    #[test]
+    fn chords_wider_than_the_keys_are_unplayable() {
+        let result = plan(&[vec![60], vec![24, 100]], &options(PlanObjective::Changes, 1));
+
+        assert!(matches!(result, Err(PlanError::Unplayable(1))));
+    }
+
+    #[test]
+    fn equal_changes_take_the_fewest_keypresses() {
+        // anything from +4 to +50 is one change
+        let plan = plan(&[vec![100]], &options(PlanObjective::Changes, 1)).unwrap();
+
+        assert_eq!(plan.transposes, vec![4]);
+        assert_eq!(plan.keypresses, 4);
+    }
+
+    #[test]
+    fn equal_keypresses_take_the_fewest_changes() {
+        // +1 then +4 is as many keypresses as +4 straight away
+        let plan = plan(&[vec![97], vec![100]], &options(PlanObjective::Keypresses, 1)).unwrap();
+
+        assert_eq!(plan.transposes, vec![4]);
+        assert_eq!(plan.changes, 1);
+    }
+
+    #[test]
+    fn primary_weight_outweighs_any_keypresses() {
+        // one change of 100 keypresses against two of one each
+        assert!(change_cost(PlanObjective::Changes, -50, 50) < 2 * change_cost(PlanObjective::Changes, 0, 1));
+        // and the other way round, two changes of one keypress against one of three
+        assert!(2 * change_cost(PlanObjective::Keypresses, 0, 1) < change_cost(PlanObjective::Keypresses, 0, 3));
+    }
+}
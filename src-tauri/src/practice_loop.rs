@@ -0,0 +1,124 @@
+use tauri::{AppHandle, Manager};
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use log::info;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::event_processing::Payload;
+use crate::{SELECTED_INDEX, TRANSPOSES};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PracticeLoop {
+    // transpose indices, next wraps from end back to start
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    // times through the loop before next carries on past end, None loops until cleared
+    pub repeats: Option<u32>,
+    pub completed: u32,
+}
+
+impl PracticeLoop {
+    fn is_active(&self) -> bool {
+        matches!((self.start, self.end), (Some(start), Some(end)) if start <= end)
+    }
+}
+
+lazy_static! {
+    static ref PRACTICE_LOOP: Mutex<PracticeLoop> = Mutex::new(PracticeLoop::default());
+}
+
+// the monitor shades start to end, a loop with only one end set isn't shaded
+fn emit_practice_loop(practice_loop: &PracticeLoop, app_handle: &AppHandle) {
+    let practice_loop = match practice_loop == &PracticeLoop::default() {
+        true => None,
+        false => Some(practice_loop),
+    };
+
+    let json = serde_json::to_string(&json!({"practice_loop": practice_loop})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+}
+
+// indices mean nothing once the transposes are replaced
+pub fn clear() {
+    *PRACTICE_LOOP.lock().unwrap() = PracticeLoop::default();
+}
+
+// the loop start bind, pressed again on the start it clears the loop
+pub fn set_loop_start(app_handle: &AppHandle) {
+    let selected_index = *SELECTED_INDEX.lock().unwrap();
+    let mut practice_loop = PRACTICE_LOOP.lock().unwrap();
+
+    if practice_loop.start == Some(selected_index) {
+        *practice_loop = PracticeLoop::default();
+    }
+    else {
+        practice_loop.start = Some(selected_index);
+        practice_loop.end = practice_loop.end.filter(|end| *end >= selected_index);
+        practice_loop.completed = 0;
+    }
+
+    info!("Practice loop {:?}", *practice_loop);
+    emit_practice_loop(&practice_loop, app_handle);
+}
+
+// the loop end bind, pressed again on the end it clears the loop
+pub fn set_loop_end(app_handle: &AppHandle) {
+    let selected_index = *SELECTED_INDEX.lock().unwrap();
+    let mut practice_loop = PRACTICE_LOOP.lock().unwrap();
+
+    if practice_loop.end == Some(selected_index) {
+        *practice_loop = PracticeLoop::default();
+    }
+    else {
+        practice_loop.end = Some(selected_index);
+        practice_loop.start = practice_loop.start.filter(|start| *start <= selected_index);
+        practice_loop.completed = 0;
+    }
+
+    info!("Practice loop {:?}", *practice_loop);
+    emit_practice_loop(&practice_loop, app_handle);
+}
+
+// backend_event {"practice_loop": {start, end, repeats}}, null clears it
+pub fn practice_loop_event(value: &Value, app_handle: &AppHandle) {
+    let transpose_count = TRANSPOSES.lock().unwrap().len();
+
+    let mut new_loop: PracticeLoop = serde_json::from_value(value.clone()).unwrap_or_default();
+    new_loop.completed = 0;
+
+    // out of range ends are dropped rather than looping onto transposes that aren't there
+    new_loop.start = new_loop.start.filter(|start| *start < transpose_count);
+    new_loop.end = new_loop.end.filter(|end| *end < transpose_count);
+    new_loop.repeats = new_loop.repeats.filter(|repeats| *repeats > 0);
+
+    let mut practice_loop = PRACTICE_LOOP.lock().unwrap();
+    *practice_loop = new_loop;
+
+    emit_practice_loop(&practice_loop, app_handle);
+}
+
+// where next goes from the selected index, B wraps back to A while the loop has repeats left
+pub fn next_index(selected_index: usize, transpose_count: usize, app_handle: &AppHandle) -> usize {
+    let mut practice_loop = PRACTICE_LOOP.lock().unwrap();
+
+    if !practice_loop.is_active() || practice_loop.end != Some(selected_index) {
+        return (selected_index + 1) % transpose_count; // circular
+    }
+
+    practice_loop.completed += 1;
+
+    let next_index = match practice_loop.repeats {
+        Some(repeats) if practice_loop.completed >= repeats => {
+            info!("Practice loop done after {} times", repeats);
+            *practice_loop = PracticeLoop::default();
+
+            (selected_index + 1) % transpose_count
+        }
+        _ => practice_loop.start.unwrap(),
+    };
+
+    emit_practice_loop(&practice_loop, app_handle);
+
+    next_index
+}
@@ -0,0 +1,202 @@
+use tauri::{AppHandle, Manager, State};
+use log::{error, info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::database::{Database, DatabaseError};
+use crate::keyboard::{set_bind, required_binds_set, BIND_NAMES};
+use crate::event_processing::Payload;
+use crate::macros::{set_macros, validate_macros, KeyMacro};
+use crate::PAUSED;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeybindProfile {
+    pub name: String,
+    // bind name -> {purpose, desc, value: {key, keyCode}, required}, as the frontend's KeyBindManager keeps it
+    pub keys: Value,
+    pub is_default: bool,
+    #[serde(default)]
+    pub macros: Vec<KeyMacro>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeybindProfileSummary {
+    pub name: String,
+    pub is_default: bool,
+}
+
+fn parse_profile(json: &str) -> (Value, Vec<KeyMacro>) {
+    // stored as {"keys": {...}} since the frontend days, keep it that way for older installs, macros sit next to the keys
+    match serde_json::from_str::<Value>(json) {
+        Ok(value) => {
+            let macros = value.get("macros")
+                .and_then(|macros| serde_json::from_value(macros.clone()).ok())
+                .unwrap_or_default();
+
+            (value.get("keys").cloned().unwrap_or(Value::Null), macros)
+        }
+        Err(err) => {
+            warn!("Corrupt keybind profile json: {}", err);
+            (Value::Null, vec![])
+        }
+    }
+}
+
+pub fn get_profile(conn: &Connection, name: &str) -> Result<Option<KeybindProfile>, DatabaseError> {
+    let profile = conn.query_row(
+        "SELECT name, json, isDefault FROM KeyBindConfig WHERE name = ?1",
+        params![name],
+        |row| {
+            let (keys, macros) = parse_profile(&row.get::<_, Option<String>>(1)?.unwrap_or_default());
+
+            Ok(KeybindProfile {
+                name: row.get(0)?,
+                keys,
+                is_default: row.get::<_, Option<bool>>(2)?.unwrap_or(false),
+                macros,
+            })
+        },
+    ).optional()?;
+
+    Ok(profile)
+}
+
+pub fn get_active_profile(conn: &Connection) -> Result<Option<KeybindProfile>, DatabaseError> {
+    let name: Option<String> = conn.query_row(
+        "SELECT name FROM KeyBindConfig ORDER BY isDefault DESC, name = 'default' DESC LIMIT 1",
+        [],
+        |row| row.get(0),
+    ).optional()?;
+
+    match name {
+        Some(name) => get_profile(conn, &name),
+        None => Ok(None),
+    }
+}
+
+// every way a profile is stored goes through here, saved from the bind manager or imported from a bundle
+pub fn put_profile(conn: &Connection, profile: &KeybindProfile) -> Result<(), DatabaseError> {
+    let bind_keycodes: Vec<u64> = profile_binds(&profile.keys).into_iter().map(|(_, keycode)| keycode).collect();
+    validate_macros(&profile.macros, &bind_keycodes)?;
+
+    let json = serde_json::to_string(&json!({"keys": profile.keys, "macros": profile.macros})).unwrap();
+
+    if profile.is_default {
+        // only one profile is restored on startup
+        conn.execute("UPDATE KeyBindConfig SET isDefault = false WHERE name != ?1", params![profile.name])?;
+    }
+
+    conn.execute(
+        "INSERT OR REPLACE INTO KeyBindConfig (name, json, isDefault) VALUES (?1, ?2, ?3)",
+        params![profile.name, json, profile.is_default],
+    )?;
+
+    Ok(())
+}
+
+pub fn list_profiles(conn: &Connection) -> Result<Vec<KeybindProfileSummary>, DatabaseError> {
+    let mut statement = conn.prepare("SELECT name, isDefault FROM KeyBindConfig ORDER BY name")?;
+    let profiles = statement
+        .query_map([], |row| Ok(KeybindProfileSummary {
+            name: row.get(0)?,
+            is_default: row.get::<_, Option<bool>>(1)?.unwrap_or(false),
+        }))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(profiles)
+}
+
+// (bind name, keycode) pairs of every bind that has a key set
+pub fn profile_binds(keys: &Value) -> Vec<(String, u64)> {
+    let mut binds = vec![];
+
+    if let Some(keys) = keys.as_object() {
+        for (name, bind) in keys {
+            let keycode = bind.get("value").and_then(|value| value.get("keyCode")).and_then(|code| code.as_u64());
+            if let Some(keycode) = keycode {
+                binds.push((name.clone(), keycode));
+            }
+        }
+    }
+
+    binds
+}
+
+// puts the active profile's keys into the bind state, so binds work before the frontend has loaded
+pub unsafe fn restore_active_profile(conn: &Connection, app_handle: &AppHandle) {
+    let profile = match get_active_profile(conn) {
+        Ok(Some(profile)) => profile,
+        Ok(None) => return,
+        Err(err) => {
+            error!("Failed to restore keybinds: {}", err);
+            return;
+        }
+    };
+
+    // binds missing from the profile must not keep a key from a previous one
+    for name in BIND_NAMES {
+        set_bind(name, None);
+    }
+
+    for (name, keycode) in profile_binds(&profile.keys) {
+        set_bind(&name, Some(keycode));
+    }
+    set_macros(profile.macros);
+
+    info!("Restored keybind profile '{}'", profile.name);
+
+    if required_binds_set() {
+        // same as the frontend does once every required keybind is set, without the resume sound on launch
+        PAUSED = false;
+
+        let json = serde_json::to_string(&json!({"paused": PAUSED})).unwrap();
+        app_handle.emit_all("frontend_event", Payload { message: json });
+    }
+}
+
+#[tauri::command]
+pub fn save_keybind_profile(database: State<Database>, name: String, keys: Value, is_default: bool, macros: Option<Vec<KeyMacro>>) -> Result<(), DatabaseError> {
+    let conn = database.conn()?;
+
+    // saving only the keys keeps the macros already stored
+    let macros = match macros {
+        Some(macros) => macros,
+        None => get_profile(&conn, &name)?.map(|profile| profile.macros).unwrap_or_default(),
+    };
+
+    put_profile(&conn, &KeybindProfile { name: name.clone(), keys, is_default, macros: macros.clone() })?;
+
+    // the active profile's macros take effect straight away, its binds already have through backend_event
+    if get_active_profile(&conn)?.map(|profile| profile.name) == Some(name) {
+        set_macros(macros);
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn load_keybind_profile(database: State<Database>, name: Option<String>) -> Result<Option<KeybindProfile>, DatabaseError> {
+    let conn = database.conn()?;
+
+    match name {
+        Some(name) => get_profile(&conn, &name),
+        None => get_active_profile(&conn),
+    }
+}
+
+#[tauri::command]
+pub fn list_keybind_profiles(database: State<Database>) -> Result<Vec<KeybindProfileSummary>, DatabaseError> {
+    let conn = database.conn()?;
+
+    list_profiles(&conn)
+}
+
+#[tauri::command]
+pub fn delete_keybind_profile(database: State<Database>, name: String) -> Result<(), DatabaseError> {
+    let conn = database.conn()?;
+    conn.execute("DELETE FROM KeyBindConfig WHERE name = ?1", params![name])?;
+
+    Ok(())
+}
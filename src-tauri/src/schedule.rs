@@ -0,0 +1,142 @@
+use tauri::{AppHandle, Manager};
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use serde_json::json;
+use crate::event_processing::Payload;
+use crate::history;
+use crate::sheet_positions::emit_current_index;
+use crate::worker::transpose_and_wait;
+use crate::{PAUSED, SELECTED_INDEX, TRANSPOSES, TRANSPOSE_ANNOTATIONS};
+
+// how often a waiting schedule checks for a pause or a stop
+const SCHEDULE_POLL: Duration = Duration::from_millis(10);
+// later times than this are left out, a day is far longer than any song and keeps Instant maths from overflowing
+const MAX_SCHEDULE_TIME: f64 = 24.0 * 60.0 * 60.0;
+
+// how early a transpose starts before its time, from settings, so the keypresses are done when the section begins
+pub static mut SCHEDULE_LEAD_TIME_MS: u64 = 150;
+
+pub static mut SCHEDULE_RUNNING: bool = false;
+// bumped every time the schedule starts or stops, older threads see the change and exit
+static mut SCHEDULE_GENERATION: u64 = 0;
+
+fn emit_schedule(app_handle: &AppHandle) {
+    let json = serde_json::to_string(&json!({"schedule": unsafe { SCHEDULE_RUNNING }})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+}
+
+// (index, seconds from the start) of every transpose with a usable time, in order of their times
+fn scheduled_transposes() -> Vec<(usize, f64)> {
+    let mut scheduled: Vec<(usize, f64)> = TRANSPOSE_ANNOTATIONS.lock().unwrap()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, annotation)| annotation.time.map(|time| (index, time)))
+        .filter(|(_, time)| time.is_finite() && (0.0..=MAX_SCHEDULE_TIME).contains(time))
+        .collect();
+
+    scheduled.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    scheduled
+}
+
+pub unsafe fn toggle_schedule(app_handle: &AppHandle) {
+    match SCHEDULE_RUNNING {
+        true => stop_schedule(app_handle),
+        false => start_schedule(app_handle),
+    }
+}
+
+pub unsafe fn stop_schedule(app_handle: &AppHandle) {
+    if !SCHEDULE_RUNNING {
+        return;
+    }
+
+    SCHEDULE_GENERATION += 1;
+    SCHEDULE_RUNNING = false;
+    info!("Schedule stopped");
+
+    emit_schedule(app_handle);
+}
+
+// the start bind is time zero, time spent paused doesn't count
+pub unsafe fn start_schedule(app_handle: &AppHandle) {
+    let scheduled = scheduled_transposes();
+    if scheduled.is_empty() {
+        warn!("None of the transposes have a time to schedule");
+        return;
+    }
+
+    SCHEDULE_GENERATION += 1;
+    SCHEDULE_RUNNING = true;
+    let generation = SCHEDULE_GENERATION;
+    let app_handle = app_handle.clone();
+
+    emit_schedule(&app_handle);
+
+    std::thread::spawn(move || {
+        info!("Schedule started with {} transposes", scheduled.len());
+
+        let mut start = Instant::now();
+        let mut paused_at: Option<Instant> = None;
+        // how late each transpose finished, to see if the schedule drifts over a song
+        let mut total_drift_ms: i64 = 0;
+
+        for (index, time) in &scheduled {
+            let due = Duration::from_secs_f64(*time);
+            let lead_time = Duration::from_millis(SCHEDULE_LEAD_TIME_MS);
+
+            loop {
+                if SCHEDULE_GENERATION != generation {
+                    return;
+                }
+
+                if PAUSED {
+                    paused_at.get_or_insert_with(Instant::now);
+                    std::thread::sleep(SCHEDULE_POLL);
+                    continue;
+                }
+
+                // the clock stood still while paused
+                if let Some(paused_at) = paused_at.take() {
+                    start += paused_at.elapsed();
+                }
+
+                let now = Instant::now();
+                let deadline = start + due.saturating_sub(lead_time);
+                if now >= deadline {
+                    break;
+                }
+
+                std::thread::sleep((deadline - now).min(SCHEDULE_POLL));
+            }
+
+            let woke_at = start.elapsed();
+
+            let new_transpose = match TRANSPOSES.lock().unwrap().get(*index) {
+                Some(new_transpose) => *new_transpose,
+                // the transposes were replaced while the schedule ran
+                None => break,
+            };
+            transpose_and_wait(new_transpose);
+            *SELECTED_INDEX.lock().unwrap() = *index;
+            history::forget_redo(&app_handle);
+            emit_current_index(*index, &app_handle);
+
+            // drift is against the transpose's own time, the lead time is how early we meant to be
+            let finished_at = start.elapsed();
+            let drift_ms = finished_at.as_millis() as i64 - due.as_millis() as i64;
+            total_drift_ms += drift_ms;
+
+            info!(
+                "Scheduled transpose {} ({:+}) due at {:.3}s, started at {:.3}s, done at {:.3}s, drift {}ms",
+                index, new_transpose, time, woke_at.as_secs_f64(), finished_at.as_secs_f64(), drift_ms,
+            );
+        }
+
+        if SCHEDULE_GENERATION == generation {
+            info!("Schedule finished, average drift {}ms", total_drift_ms / scheduled.len() as i64);
+
+            SCHEDULE_RUNNING = false;
+            emit_schedule(&app_handle);
+        }
+    });
+}
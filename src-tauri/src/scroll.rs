@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+use log::error;
+use rdev::{simulate, EventType};
+use crate::metronome::sleep_until;
+use crate::keyboard::KEY_LISTEN;
+use crate::{PAUSED, SCROLL_VALUE};
+
+// most wheel events one smooth scroll is split into
+const SMOOTH_STEPS: i64 = 8;
+// a held scroll bind stops repeating after this, in case its release never reached us
+const SCROLL_HOLD_LIMIT: Duration = Duration::from_secs(30);
+
+// time between two scrolls while a scroll bind is held, from settings, 0 scrolls once per press
+pub static mut SCROLL_REPEAT_MS: u64 = 150;
+// a scroll is sent as several small wheel events over SMOOTH_SCROLL_MS, from settings
+pub static mut SMOOTH_SCROLL: bool = false;
+pub static mut SMOOTH_SCROLL_MS: u64 = 100;
+
+// the held scroll bind's direction, 1 up and -1 down
+static mut SCROLL_DIRECTION: i64 = 0;
+// bumped every time a scroll bind is pressed or released, older threads see the change and exit
+static mut SCROLL_GENERATION: u64 = 0;
+
+fn send_wheel(delta_y: i64) {
+    if let Err(err) = simulate(&EventType::Wheel { delta_x: 0, delta_y }) {
+        error!("Failed to send scroll event: {:?}", err);
+    }
+}
+
+// one scroll of SCROLL_VALUE, direction 1 is up and -1 down
+pub unsafe fn scroll_bind_event(direction: i64) {
+    if PAUSED || SCROLL_VALUE == 0 {
+        return;
+    }
+
+    if !SMOOTH_SCROLL {
+        send_wheel(direction * SCROLL_VALUE);
+        return;
+    }
+
+    // the remainder goes to the first steps, so the steps always add up to SCROLL_VALUE
+    let steps = SMOOTH_STEPS.min(SCROLL_VALUE);
+    let step_duration = Duration::from_millis(SMOOTH_SCROLL_MS) / steps as u32;
+    let mut next_step = Instant::now();
+
+    for step in 0..steps {
+        let delta = SCROLL_VALUE / steps + i64::from(step < SCROLL_VALUE % steps);
+        send_wheel(direction * delta);
+
+        next_step += step_duration;
+        sleep_until(next_step);
+    }
+}
+
+// the scroll up/down binds, scrolls straight away and again every SCROLL_REPEAT_MS until released,
+// pausing or listening for a new bind also stops it since the release is swallowed then
+pub unsafe fn start_scrolling(direction: i64) {
+    SCROLL_GENERATION += 1;
+    SCROLL_DIRECTION = direction;
+    let generation = SCROLL_GENERATION;
+
+    // a smooth scroll takes a while, the key listener mustn't wait for it
+    std::thread::spawn(move || unsafe {
+        let started = Instant::now();
+        let mut next_scroll = started;
+
+        loop {
+            scroll_bind_event(direction);
+
+            if SCROLL_REPEAT_MS == 0 {
+                break;
+            }
+
+            // a smooth scroll longer than the repeat doesn't pile up scrolls to catch up on
+            next_scroll = (next_scroll + Duration::from_millis(SCROLL_REPEAT_MS)).max(Instant::now());
+            sleep_until(next_scroll);
+
+            if SCROLL_GENERATION != generation || PAUSED || KEY_LISTEN || started.elapsed() >= SCROLL_HOLD_LIMIT {
+                break;
+            }
+        }
+    });
+}
+
+// releasing the other direction's bind keeps the one still held scrolling
+pub unsafe fn stop_scrolling(direction: i64) {
+    if SCROLL_DIRECTION == direction {
+        SCROLL_GENERATION += 1;
+        SCROLL_DIRECTION = 0;
+    }
+}
@@ -0,0 +1,216 @@
+use tauri::{AppHandle, Manager, State};
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use log::{error, info};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use crate::audio::{Sound, play_sound};
+use crate::database::{Database, DatabaseError, unix_timestamp};
+use crate::event_processing::Payload;
+use crate::sheets::play_sheet;
+use crate::PAUSED;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Setlist {
+    // None until the setlist is saved
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub name: String,
+    // in performance order, a sheet may appear more than once
+    #[serde(default)]
+    pub sheet_ids: Vec<i64>,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+struct ActiveSetlist {
+    id: i64,
+    sheet_ids: Vec<i64>,
+    position: usize,
+}
+
+lazy_static! {
+    static ref ACTIVE_SETLIST: Mutex<Option<ActiveSetlist>> = Mutex::new(None);
+}
+
+fn setlist_sheet_ids(conn: &Connection, id: i64) -> Result<Vec<i64>, DatabaseError> {
+    let mut statement = conn.prepare("SELECT sheetId FROM SetlistSheet WHERE setlistId = ?1 ORDER BY position")?;
+    let sheet_ids = statement
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, _>>()?;
+
+    Ok(sheet_ids)
+}
+
+pub fn get_setlist(conn: &Connection, id: i64) -> Result<Setlist, DatabaseError> {
+    let (name, created_at) = conn.query_row(
+        "SELECT name, createdAt FROM Setlist WHERE id = ?1",
+        params![id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+        .optional()?
+        .ok_or(DatabaseError::NotFound(format!("Setlist {}", id)))?;
+
+    Ok(Setlist { id: Some(id), name, sheet_ids: setlist_sheet_ids(conn, id)?, created_at })
+}
+
+fn write_setlist_sheets(tx: &Connection, id: i64, sheet_ids: &[i64]) -> Result<(), DatabaseError> {
+    tx.execute("DELETE FROM SetlistSheet WHERE setlistId = ?1", params![id])?;
+    for (position, sheet_id) in sheet_ids.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO SetlistSheet (setlistId, position, sheetId) VALUES (?1, ?2, ?3)",
+            params![id, position as i64, sheet_id],
+        )?;
+    }
+
+    Ok(())
+}
+
+// a new setlist, for callers already inside a transaction
+pub fn insert_setlist(tx: &Connection, name: &str, sheet_ids: &[i64]) -> Result<i64, DatabaseError> {
+    tx.execute("INSERT INTO Setlist (name, createdAt) VALUES (?1, ?2)", params![name, unix_timestamp()])?;
+    let id = tx.last_insert_rowid();
+    write_setlist_sheets(tx, id, sheet_ids)?;
+
+    Ok(id)
+}
+
+pub fn put_setlist(conn: &mut Connection, setlist: Setlist) -> Result<Setlist, DatabaseError> {
+    if setlist.name.trim().is_empty() {
+        return Err(DatabaseError::Invalid("Setlist name must not be empty".to_string()));
+    }
+
+    let tx = conn.transaction()?;
+
+    let id = match setlist.id {
+        Some(id) => {
+            if tx.execute("UPDATE Setlist SET name = ?1 WHERE id = ?2", params![setlist.name, id])? == 0 {
+                return Err(DatabaseError::NotFound(format!("Setlist {}", id)));
+            }
+
+            id
+        }
+        None => {
+            tx.execute("INSERT INTO Setlist (name, createdAt) VALUES (?1, ?2)", params![setlist.name, unix_timestamp()])?;
+            tx.last_insert_rowid()
+        }
+    };
+
+    write_setlist_sheets(&tx, id, &setlist.sheet_ids)?;
+    tx.commit()?;
+
+    get_setlist(conn, id)
+}
+
+pub fn all_setlists(conn: &Connection) -> Result<Vec<Setlist>, DatabaseError> {
+    let mut statement = conn.prepare("SELECT id FROM Setlist ORDER BY name COLLATE NOCASE")?;
+    let ids = statement
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, _>>()?;
+
+    ids.into_iter().map(|id| get_setlist(conn, id)).collect()
+}
+
+unsafe fn play_position(conn: &Connection, active: &ActiveSetlist, transpose_game: bool, app_handle: &AppHandle) -> Result<(), DatabaseError> {
+    play_sheet(conn, active.sheet_ids[active.position], transpose_game, app_handle)?;
+
+    let json = serde_json::to_string(&json!({"setlist": {
+        "id": active.id,
+        "position": active.position,
+        "length": active.sheet_ids.len(),
+    }})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+
+    Ok(())
+}
+
+// activates the setlist and plays the song at `position`, with transpose_game the game is transposed to its first transpose
+pub unsafe fn start_setlist_at(conn: &Connection, id: i64, position: usize, transpose_game: bool, app_handle: &AppHandle) -> Result<(), DatabaseError> {
+    let setlist = get_setlist(conn, id)?;
+    if setlist.sheet_ids.is_empty() {
+        return Err(DatabaseError::Invalid(format!("Setlist '{}' has no sheets", setlist.name)));
+    }
+
+    let active = ActiveSetlist { id, position: position.min(setlist.sheet_ids.len() - 1), sheet_ids: setlist.sheet_ids };
+    play_position(conn, &active, transpose_game, app_handle)?;
+    info!("Started setlist '{}'", setlist.name);
+
+    *ACTIVE_SETLIST.lock().unwrap() = Some(active);
+
+    Ok(())
+}
+
+// moves through the active setlist by `step` songs, stopping at either end
+pub unsafe fn change_song(step: isize, app_handle: &AppHandle) {
+    if PAUSED {
+        return;
+    }
+
+    let database = match app_handle.try_state::<Database>() {
+        Some(database) => database,
+        None => return,
+    };
+    let conn = match database.conn() {
+        Ok(conn) => conn,
+        Err(err) => {
+            error!("Failed to change song: {}", err);
+            return;
+        }
+    };
+
+    let mut active_setlist = ACTIVE_SETLIST.lock().unwrap();
+    let active = match active_setlist.as_mut() {
+        Some(active) => active,
+        None => return,
+    };
+
+    let position = active.position as isize + step;
+    if position < 0 || position >= active.sheet_ids.len() as isize {
+        return;
+    }
+
+    active.position = position as usize;
+    play_sound(Sound::SongChange, app_handle.clone());
+
+    // the transpose is only queued for the key worker, the database isn't held while its keys are sent
+    if let Err(err) = play_position(&conn, active, true, app_handle) {
+        error!("Failed to change song: {}", err);
+    }
+}
+
+#[tauri::command]
+pub fn save_setlist(database: State<Database>, setlist: Setlist) -> Result<Setlist, DatabaseError> {
+    let mut conn = database.conn()?;
+
+    put_setlist(&mut conn, setlist)
+}
+
+#[tauri::command]
+pub fn list_setlists(database: State<Database>) -> Result<Vec<Setlist>, DatabaseError> {
+    let conn = database.conn()?;
+
+    all_setlists(&conn)
+}
+
+#[tauri::command]
+pub fn delete_setlist(database: State<Database>, id: i64) -> Result<(), DatabaseError> {
+    let conn = database.conn()?;
+    conn.execute("DELETE FROM Setlist WHERE id = ?1", params![id])?;
+
+    let mut active_setlist = ACTIVE_SETLIST.lock().unwrap();
+    if active_setlist.as_ref().map(|active| active.id) == Some(id) {
+        *active_setlist = None;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn start_setlist(app_handle: AppHandle, database: State<Database>, id: i64, position: Option<usize>) -> Result<(), DatabaseError> {
+    let conn = database.conn()?;
+
+    // started from our own window, keys sent now would land there instead of the game, which is assumed to be on the song's first transpose
+    unsafe { start_setlist_at(&conn, id, position.unwrap_or(0), false, &app_handle) }
+}
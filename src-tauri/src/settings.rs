@@ -0,0 +1,392 @@
+use tauri::{AppHandle, Manager, State};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::audio::{MUTED, VOLUME};
+use crate::auto_scroll::{AUTO_SCROLL_INTERVAL_MS, MAX_AUTO_SCROLL_INTERVAL_MS, MIN_AUTO_SCROLL_INTERVAL_MS};
+use crate::database::unix_timestamp;
+use crate::event_processing::Payload;
+use crate::keyboard::TRANSPOSE_DEBOUNCE_MS;
+use crate::schedule::SCHEDULE_LEAD_TIME_MS;
+use crate::scroll::{SCROLL_REPEAT_MS, SMOOTH_SCROLL, SMOOTH_SCROLL_MS};
+use crate::layout::{Layout, LayoutSetting};
+use crate::virtual_transpose::{set_layout, start_grab, OutOfRange, OUT_OF_RANGE, VIRTUAL_TRANSPOSE};
+use crate::SCROLL_VALUE;
+
+const SETTINGS_FILE: &str = "settings.json";
+pub const SETTINGS_VERSION: u32 = 2;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("app data directory could not be resolved")]
+    NoDataDir,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl Serialize for SettingsError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AudioSettings {
+    pub muted: bool,
+    pub volume: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings { muted: false, volume: 0.3 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ScrollSettings {
+    // wheel delta sent by the scroll binds
+    pub value: i64,
+    // time between scrolls while a scroll bind is held, 0 scrolls once per press
+    pub repeat_ms: u64,
+    // each scroll as several small wheel events spread over smooth_ms
+    pub smooth: bool,
+    pub smooth_ms: u64,
+    // time between two scrolls of the auto scroll bind, its faster/slower binds save their changes here
+    pub auto_interval_ms: u64,
+}
+
+impl Default for ScrollSettings {
+    fn default() -> Self {
+        ScrollSettings { value: 0, repeat_ms: 150, smooth: false, smooth_ms: 100, auto_interval_ms: 2000 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DebounceSettings {
+    // minimum time between two next/previous transposes
+    pub transpose_ms: u64,
+}
+
+impl Default for DebounceSettings {
+    fn default() -> Self {
+        DebounceSettings { transpose_ms: 100 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ScheduleSettings {
+    // timed transposes start this early, so they're done by their time
+    pub lead_time_ms: u64,
+}
+
+impl Default for ScheduleSettings {
+    fn default() -> Self {
+        ScheduleSettings { lead_time_ms: 150 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct VirtualTransposeSettings {
+    // for games without transpose keys, transpose up/down binds go unused while on
+    pub enabled: bool,
+    pub out_of_range: OutOfRange,
+    pub layout: LayoutSetting,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RangeSettings {
+    // lowest and highest transpose the game allows
+    pub min_transpose: i32,
+    pub max_transpose: i32,
+}
+
+impl Default for RangeSettings {
+    fn default() -> Self {
+        RangeSettings { min_transpose: -50, max_transpose: 50 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WindowSettings {
+    // sheet viewer
+    pub transparency: f32,
+    pub zoom_step_size: f32,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings { transparency: 5.0, zoom_step_size: 0.1 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    pub version: u32,
+    pub audio: AudioSettings,
+    pub scroll: ScrollSettings,
+    pub debounce: DebounceSettings,
+    pub schedule: ScheduleSettings,
+    pub virtual_transpose: VirtualTransposeSettings,
+    pub range: RangeSettings,
+    pub window: WindowSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: SETTINGS_VERSION,
+            audio: AudioSettings::default(),
+            scroll: ScrollSettings::default(),
+            debounce: DebounceSettings::default(),
+            schedule: ScheduleSettings::default(),
+            virtual_transpose: VirtualTransposeSettings::default(),
+            range: RangeSettings::default(),
+            window: WindowSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if !(0.0..=2.0).contains(&self.audio.volume) {
+            return Err(SettingsError::Invalid("Volume must be between 0 and 2".to_string()));
+        }
+
+        if !(0..=200).contains(&self.scroll.value) {
+            return Err(SettingsError::Invalid("Scroll must be between 0 and 200".to_string()));
+        }
+
+        if self.scroll.repeat_ms != 0 && !(20..=2000).contains(&self.scroll.repeat_ms) {
+            return Err(SettingsError::Invalid("Scroll repeat must be 0 or between 20 and 2000ms".to_string()));
+        }
+
+        if self.scroll.smooth_ms > 1000 {
+            return Err(SettingsError::Invalid("Smooth scrolling must not take longer than 1000ms".to_string()));
+        }
+
+        if !(MIN_AUTO_SCROLL_INTERVAL_MS..=MAX_AUTO_SCROLL_INTERVAL_MS).contains(&self.scroll.auto_interval_ms) {
+            return Err(SettingsError::Invalid(format!(
+                "Auto scroll must be every {} to {}ms", MIN_AUTO_SCROLL_INTERVAL_MS, MAX_AUTO_SCROLL_INTERVAL_MS,
+            )));
+        }
+
+        if self.debounce.transpose_ms > 1000 {
+            return Err(SettingsError::Invalid("Debounce must not exceed 1000ms".to_string()));
+        }
+
+        if self.schedule.lead_time_ms > 2000 {
+            return Err(SettingsError::Invalid("Schedule lead time must not exceed 2000ms".to_string()));
+        }
+
+        if let Err(err) = self.virtual_transpose.layout.layout() {
+            return Err(SettingsError::Invalid(format!("Layout is not valid: {}", err)));
+        }
+
+        if self.range.min_transpose > self.range.max_transpose
+            || self.range.min_transpose < -50
+            || self.range.max_transpose > 50
+        {
+            return Err(SettingsError::Invalid("Transpose range must be within -/+50, lowest first".to_string()));
+        }
+
+        if !(0.0..=10.0).contains(&self.window.transparency) {
+            return Err(SettingsError::Invalid("Transparency must be between 0 and 10".to_string()));
+        }
+
+        if !(0.01..=10.0).contains(&self.window.zoom_step_size) {
+            return Err(SettingsError::Invalid("Zoom step size must be between 0.01 and 10".to_string()));
+        }
+
+        Ok(())
+    }
+
+    // puts the settings the backend acts on into effect
+    pub unsafe fn apply(&self) {
+        MUTED = self.audio.muted;
+        VOLUME = self.audio.volume;
+        SCROLL_VALUE = self.scroll.value;
+        SCROLL_REPEAT_MS = self.scroll.repeat_ms;
+        SMOOTH_SCROLL = self.scroll.smooth;
+        SMOOTH_SCROLL_MS = self.scroll.smooth_ms;
+        AUTO_SCROLL_INTERVAL_MS = self.scroll.auto_interval_ms;
+        TRANSPOSE_DEBOUNCE_MS = self.debounce.transpose_ms;
+        SCHEDULE_LEAD_TIME_MS = self.schedule.lead_time_ms;
+
+        VIRTUAL_TRANSPOSE = self.virtual_transpose.enabled;
+        OUT_OF_RANGE = self.virtual_transpose.out_of_range;
+        set_layout(self.virtual_transpose.layout.layout().unwrap_or_else(|_| Layout::vp61()));
+        if VIRTUAL_TRANSPOSE {
+            start_grab();
+        }
+    }
+}
+
+// upgrades[n] takes a version n + 1 file to version n + 2
+const UPGRADES: &[fn(Value) -> Value] = &[
+    upgrade_v1,
+];
+
+// v1 is the flat {muted, volume} the frontend used to write
+// scroll and window settings were in localStorage then, the frontend moves those over itself
+fn upgrade_v1(v1: Value) -> Value {
+    json!({
+        "version": 2,
+        "audio": {
+            "muted": v1.get("muted").cloned().unwrap_or(json!(false)),
+            "volume": v1.get("volume").cloned().unwrap_or(json!(0.3)),
+        },
+    })
+}
+
+fn upgrade(mut value: Value) -> Value {
+    let version = value.get("version").and_then(|version| version.as_u64()).unwrap_or(1) as usize;
+
+    for upgrade in UPGRADES.iter().skip(version.saturating_sub(1)) {
+        value = upgrade(value);
+    }
+
+    value
+}
+
+// recursively overwrites base with the fields in patch
+fn merge(base: &mut Value, patch: &Value) {
+    match (base.as_object_mut(), patch.as_object()) {
+        (Some(base), Some(patch)) => {
+            for (key, value) in patch {
+                merge(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        _ => *base = patch.clone(),
+    }
+}
+
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+}
+
+pub fn settings_path(app_handle: &AppHandle) -> Result<PathBuf, SettingsError> {
+    let dir = app_handle.path_resolver().app_data_dir().ok_or(SettingsError::NoDataDir)?;
+    std::fs::create_dir_all(&dir)?;
+
+    Ok(dir.join(SETTINGS_FILE))
+}
+
+fn parse(contents: &str) -> Result<Settings, SettingsError> {
+    let settings: Settings = serde_json::from_value(upgrade(serde_json::from_str(contents)?))?;
+    settings.validate()?;
+
+    Ok(settings)
+}
+
+// moves a file we can't read out of the way, so the user can still recover it by hand
+fn quarantine(path: &Path) {
+    let quarantined = path.with_extension(format!("corrupt-{}.json", unix_timestamp()));
+
+    match std::fs::rename(path, &quarantined) {
+        Ok(()) => warn!("Corrupt settings moved to {}", quarantined.display()),
+        Err(err) => warn!("Failed to quarantine corrupt settings: {}", err),
+    }
+}
+
+// write then rename, a crash mid-write never leaves a half written settings file
+fn write_atomic(path: &Path, settings: &Settings) -> Result<(), SettingsError> {
+    let temp_path = path.with_extension("json.tmp");
+    std::fs::write(&temp_path, serde_json::to_string_pretty(settings)?)?;
+    std::fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
+
+// only a missing file means first run, any other read error is passed on rather than overwriting settings we couldn't read
+pub fn load(path: &Path) -> Result<Settings, SettingsError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(err) => return Err(err.into()),
+    };
+
+    match parse(&contents) {
+        Ok(settings) => Ok(settings),
+        Err(err) => {
+            warn!("Failed to read settings: {}", err);
+            quarantine(path);
+            Ok(Settings::default())
+        }
+    }
+}
+
+impl SettingsStore {
+    pub fn open(app_handle: &AppHandle) -> Result<SettingsStore, SettingsError> {
+        let path = settings_path(app_handle)?;
+        let settings = load(&path)?;
+
+        // rewrite straight away, so upgraded or defaulted settings are on disk
+        write_atomic(&path, &settings)?;
+        info!("Loaded settings version {}", settings.version);
+
+        Ok(SettingsStore { path, settings: Mutex::new(settings) })
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn replace(&self, settings: Settings) -> Result<Settings, SettingsError> {
+        let settings = Settings { version: SETTINGS_VERSION, ..settings };
+        settings.validate()?;
+
+        write_atomic(&self.path, &settings)?;
+        *self.settings.lock().unwrap() = settings.clone();
+
+        Ok(settings)
+    }
+
+    pub fn patch(&self, patch: &Value) -> Result<Settings, SettingsError> {
+        let mut settings = self.settings.lock().unwrap();
+
+        let mut value = serde_json::to_value(&*settings)?;
+        merge(&mut value, patch);
+
+        let mut patched: Settings = serde_json::from_value(value)?;
+        patched.version = SETTINGS_VERSION;
+        patched.validate()?;
+
+        write_atomic(&self.path, &patched)?;
+        *settings = patched.clone();
+
+        Ok(patched)
+    }
+}
+
+#[tauri::command]
+pub fn get_settings(store: State<SettingsStore>) -> Settings {
+    store.get()
+}
+
+#[tauri::command]
+pub fn patch_settings(app_handle: AppHandle, store: State<SettingsStore>, patch: Value) -> Result<Settings, SettingsError> {
+    let settings = store.patch(&patch)?;
+    unsafe { settings.apply(); }
+
+    // keeps every window in sync
+    let json = serde_json::to_string(&json!({"settings": settings})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+
+    Ok(settings)
+}
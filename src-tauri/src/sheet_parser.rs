@@ -0,0 +1,226 @@
+use serde::Serialize;
+
+// words that can start a marker, longest first so "transpose" isn't read as "t" + "ranspose"
+const MARKER_KEYWORDS: &[&str] = &["transpose", "t"];
+// t is a note key too, so "t" only starts a marker with one of these after it, "[t0]" is a chord
+const SHORT_KEYWORD_SEPARATORS: &[char] = &['+', '-', ':', '='];
+// same for the optional measure after the transpose, "[Transpose: +2, measure 32]"
+const MEASURE_KEYWORDS: &[&str] = &["measure", "m."];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransposeMarker {
+    pub transpose: i32,
+    // measure number the marker is at, for sheets imported from notation
+    pub measure: Option<String>,
+    // character offsets into the sheet, not bytes, so the frontend can slice with them
+    pub start: usize,
+    pub end: usize,
+    // 1 based
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedSheet {
+    pub transposes: Vec<i32>,
+    pub markers: Vec<TransposeMarker>,
+}
+
+impl ParsedSheet {
+    fn from_spans(text: &str, spans: Vec<(i32, Option<String>, usize, usize)>) -> ParsedSheet {
+        let markers: Vec<TransposeMarker> = spans.into_iter()
+            .map(|(transpose, measure, start, end)| TransposeMarker {
+                transpose,
+                measure,
+                start: text[..start].chars().count(),
+                end: text[..end].chars().count(),
+                line: text[..start].matches('\n').count() + 1,
+            })
+            .collect();
+
+        ParsedSheet { transposes: markers.iter().map(|marker| marker.transpose).collect(), markers }
+    }
+}
+
+// "measure 32", "m. 12a"
+fn parse_measure(text: &str) -> Option<String> {
+    let lowercase = text.to_ascii_lowercase();
+    let keyword = MEASURE_KEYWORDS.iter().find(|keyword| lowercase.starts_with(*keyword))?;
+    let measure = text[keyword.len()..].trim();
+
+    match measure.is_empty() || measure.contains(char::is_whitespace) {
+        true => None,
+        false => Some(measure.to_string()),
+    }
+}
+
+// reads "[Transpose: +2]", "(T-3)", "[t = 0]" and the like, brackets must match and stay on one line
+// returns the transpose, its measure if any and the marker's length in bytes
+fn parse_marker(rest: &str) -> Option<(i32, Option<String>, usize)> {
+    let close = match rest.chars().next()? {
+        '[' => ']',
+        '(' => ')',
+        _ => return None,
+    };
+
+    let line = rest.split('\n').next()?;
+    let inner_end = line.find(close)?;
+    let inner = line[1..inner_end].trim();
+
+    let keyword = MARKER_KEYWORDS.iter().find(|keyword| inner.to_ascii_lowercase().starts_with(*keyword))?;
+    let after_keyword = inner[keyword.len()..].trim_start();
+    if *keyword == "t" && !after_keyword.starts_with(SHORT_KEYWORD_SEPARATORS) {
+        return None;
+    }
+
+    let value = after_keyword.trim_start_matches([':', '=']).trim_start();
+
+    let (transpose, measure) = match value.split_once(',') {
+        Some((transpose, measure)) => (transpose.trim_end(), Some(parse_measure(measure.trim())?)),
+        None => (value, None),
+    };
+
+    Some((transpose.parse::<i32>().ok()?, measure, inner_end + close.len_utf8()))
+}
+
+// a bare list like "0 -1 +1, 1", what users type into the transposes input
+// every number in it counts, a stray word or typo between them is skipped rather than losing the list
+fn parse_plain_list(text: &str) -> ParsedSheet {
+    let bytes = text.as_bytes();
+    let mut spans = vec![];
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let signed = matches!(bytes[index], b'+' | b'-') && bytes.get(index + 1).is_some_and(u8::is_ascii_digit);
+        if !signed && !bytes[index].is_ascii_digit() {
+            index += 1;
+            continue;
+        }
+
+        let start = index;
+        index += 1;
+        while bytes.get(index).is_some_and(u8::is_ascii_digit) {
+            index += 1;
+        }
+
+        // too many digits for any transpose
+        if let Ok(transpose) = text[start..index].parse::<i32>() {
+            spans.push((transpose, None, start, index));
+        }
+    }
+
+    ParsedSheet::from_spans(text, spans)
+}
+
+// finds the transposes in a sheet, in the order they appear
+// only markers count, so numbers in titles, notes or the keys themselves are left alone
+pub fn parse_sheet(text: &str) -> ParsedSheet {
+    let mut spans = vec![];
+    let mut index = 0;
+
+    while let Some(found) = text[index..].find(['[', '(']) {
+        let start = index + found;
+
+        match parse_marker(&text[start..]) {
+            Some((transpose, measure, length)) => {
+                spans.push((transpose, measure, start, start + length));
+                index = start + length;
+            }
+            None => index = start + 1,
+        }
+    }
+
+    ParsedSheet::from_spans(text, spans)
+}
+
+#[tauri::command]
+pub fn parse_sheet_transposes(text: String) -> ParsedSheet {
+    parse_sheet(&text)
+}
+
+// for the transposes input when the text is neither a sheet nor a transpose expression
+#[tauri::command]
+pub fn parse_transpose_list(text: String) -> ParsedSheet {
+    parse_plain_list(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(transpose: i32, measure: Option<&str>, start: usize, end: usize, line: usize) -> TransposeMarker {
+        TransposeMarker { transpose, measure: measure.map(str::to_string), start, end, line }
+    }
+
+    #[test]
+    fn bracketed_markers() {
+        let parsed = parse_sheet("[Transpose: +2]\ntuo\n[t = 0]");
+
+        assert_eq!(parsed.transposes, vec![2, 0]);
+        assert_eq!(parsed.markers, vec![marker(2, None, 0, 15, 1), marker(0, None, 20, 27, 3)]);
+    }
+
+    #[test]
+    fn parenthesised_markers() {
+        assert_eq!(parse_sheet("(T-3) tuo (transpose: 12)").transposes, vec![-3, 12]);
+    }
+
+    #[test]
+    fn markers_with_a_measure() {
+        let parsed = parse_sheet("[Transpose: +2, measure 32] [T-1, m. 12a]");
+
+        assert_eq!(parsed.transposes, vec![2, -1]);
+        assert_eq!(parsed.markers[0].measure.as_deref(), Some("32"));
+        assert_eq!(parsed.markers[1].measure.as_deref(), Some("12a"));
+    }
+
+    #[test]
+    fn malformed_markers_are_left_alone() {
+        // mismatched brackets, a measure with a space, a marker split over two lines, no number
+        let parsed = parse_sheet("[T+1) [T+2, measure 3 4] [T\n+3] [Transpose] (T+4)");
+
+        assert_eq!(parsed.transposes, vec![4]);
+    }
+
+    #[test]
+    fn numbers_outside_markers_are_ignored() {
+        let parsed = parse_sheet("Song 2\n[T: +1]\n8 9 0\n(T-1)");
+
+        assert_eq!(parsed.transposes, vec![1, -1]);
+        assert_eq!(parsed.markers.iter().map(|marker| marker.line).collect::<Vec<_>>(), vec![2, 4]);
+    }
+
+    #[test]
+    fn offsets_count_characters() {
+        let parsed = parse_sheet("é♪ [T+1]");
+
+        assert_eq!(parsed.markers, vec![marker(1, None, 3, 8, 1)]);
+    }
+
+    #[test]
+    fn sheets_without_markers_have_no_transposes() {
+        assert_eq!(parse_sheet("t y u 1 2 [35]"), ParsedSheet::default());
+    }
+
+    #[test]
+    fn t_with_a_number_straight_after_is_a_chord() {
+        assert_eq!(parse_sheet("[t0] (t 0) [T3]"), ParsedSheet::default());
+        // the long keyword doesn't need a separator
+        assert_eq!(parse_sheet("[t:0] [t +1] [transpose 2]").transposes, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn plain_list() {
+        let parsed = parse_plain_list("0 -1 +1, 1");
+
+        assert_eq!(parsed.transposes, vec![0, -1, 1, 1]);
+        assert_eq!(parsed.markers[2], marker(1, None, 5, 7, 1));
+    }
+
+    #[test]
+    fn plain_list_skips_what_isnt_a_number() {
+        assert_eq!(parse_plain_list("0, x, 2\n-3 - + 99999999999 4a").transposes, vec![0, 2, -3, 4]);
+        assert_eq!(parse_plain_list("no transposes here").transposes, Vec::<i32>::new());
+    }
+}
@@ -0,0 +1,71 @@
+use tauri::{AppHandle, Manager};
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use serde_json::json;
+use crate::event_processing::Payload;
+use crate::sheet_parser::{parse_sheet, TransposeMarker};
+use crate::{SELECTED_INDEX, TRANSPOSES, transpose_annotation};
+
+#[derive(Default)]
+struct SheetPositions {
+    // markers of the sheet in the viewer, empty for images or no sheet
+    markers: Vec<TransposeMarker>,
+    // positions[i] is where transpose i is in the sheet
+    positions: Vec<Option<TransposeMarker>>,
+}
+
+lazy_static! {
+    static ref SHEET_POSITIONS: Mutex<SheetPositions> = Mutex::new(SheetPositions::default());
+}
+
+// maps each transpose to a marker only when the sheet's markers are the transposes in use,
+// anything else would highlight the wrong section
+fn map_positions(markers: &[TransposeMarker], transposes: &[i32]) -> Vec<Option<TransposeMarker>> {
+    let matches = markers.len() == transposes.len()
+        && markers.iter().zip(transposes).all(|(marker, transpose)| marker.transpose == *transpose);
+
+    match matches {
+        true => markers.iter().cloned().map(Some).collect(),
+        false => vec![None; transposes.len()],
+    }
+}
+
+// called with TRANSPOSES locked whenever the transposes are replaced
+pub fn sync_transposes(transposes: &[i32]) {
+    let mut sheet_positions = SHEET_POSITIONS.lock().unwrap();
+    sheet_positions.positions = map_positions(&sheet_positions.markers, transposes);
+}
+
+// the sheet text now shown in the viewer, None when it's cleared or an image
+pub fn set_sheet_text(text: Option<&str>) {
+    let transposes = TRANSPOSES.lock().unwrap();
+    let mut sheet_positions = SHEET_POSITIONS.lock().unwrap();
+
+    sheet_positions.markers = text.map(|text| parse_sheet(text).markers).unwrap_or_default();
+    sheet_positions.positions = map_positions(&sheet_positions.markers, &transposes);
+}
+
+pub fn sheet_position(index: usize) -> Option<TransposeMarker> {
+    SHEET_POSITIONS.lock().unwrap().positions.get(index).cloned().flatten()
+}
+
+// every current_index change goes through here, so the viewer can follow along in the sheet
+pub fn emit_current_index(index: usize, app_handle: &AppHandle) {
+    let annotation = transpose_annotation(index);
+
+    let json = serde_json::to_string(&json!({
+        "current_index": index,
+        "sheet_position": sheet_position(index),
+        "label": annotation.label,
+        "note": annotation.note,
+    })).unwrap();
+
+    app_handle.emit_all("frontend_event", Payload { message: json });
+}
+
+pub fn sheet_text_event(text: Option<&str>, app_handle: &AppHandle) {
+    set_sheet_text(text);
+
+    let selected_index = *SELECTED_INDEX.lock().unwrap();
+    emit_current_index(selected_index, app_handle);
+}
@@ -0,0 +1,249 @@
+use tauri::{AppHandle, Manager, State};
+use std::path::Path;
+use log::{error, info};
+use base64::Engine;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use crate::database::{Database, DatabaseError, unix_timestamp};
+use crate::event_processing::Payload;
+use crate::sheet_positions::{emit_current_index, set_sheet_text};
+use crate::{replace_transposes, transpose, TransposeAnnotation};
+
+// same limit the transposes input enforces
+const MAX_TRANSPOSE: i32 = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sheet {
+    // None until the sheet is saved
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub title: String,
+    #[serde(default)]
+    pub artist: Option<String>,
+    // sheet text, or None for image sheets
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub image_path: Option<String>,
+    #[serde(default)]
+    pub transposes: Vec<i32>,
+    // annotations[i] belongs to transposes[i], may be shorter
+    #[serde(default)]
+    pub annotations: Vec<TransposeAnnotation>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub last_played_at: Option<i64>,
+}
+
+fn validate(sheet: &Sheet) -> Result<(), DatabaseError> {
+    if sheet.title.trim().is_empty() {
+        return Err(DatabaseError::Invalid("Sheet title must not be empty".to_string()));
+    }
+
+    if !sheet.transposes.iter().all(|transpose| transpose.abs() <= MAX_TRANSPOSE) {
+        return Err(DatabaseError::Invalid(format!("Transposes must not exceed or fall below -/+{}", MAX_TRANSPOSE)));
+    }
+
+    if sheet.annotations.len() > sheet.transposes.len() {
+        return Err(DatabaseError::Invalid("There are more transpose annotations than transposes".to_string()));
+    }
+
+    Ok(())
+}
+
+fn sheet_from_row(row: &Row) -> rusqlite::Result<Sheet> {
+    let transposes: String = row.get("transposes")?;
+    let annotations: String = row.get("transposeAnnotations")?;
+
+    Ok(Sheet {
+        id: row.get("id")?,
+        title: row.get("title")?,
+        artist: row.get("artist")?,
+        content: row.get("content")?,
+        image_path: row.get("imagePath")?,
+        transposes: serde_json::from_str(&transposes).unwrap_or_default(),
+        annotations: serde_json::from_str(&annotations).unwrap_or_default(),
+        tags: vec![],
+        created_at: row.get("createdAt")?,
+        last_played_at: row.get("lastPlayedAt")?,
+    })
+}
+
+fn sheet_tags(conn: &Connection, id: i64) -> Result<Vec<String>, DatabaseError> {
+    let mut statement = conn.prepare("SELECT tag FROM SheetTag WHERE sheetId = ?1 ORDER BY tag")?;
+    let tags = statement
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+
+    Ok(tags)
+}
+
+fn set_sheet_tags(conn: &Connection, id: i64, tags: &[String]) -> Result<(), DatabaseError> {
+    conn.execute("DELETE FROM SheetTag WHERE sheetId = ?1", params![id])?;
+
+    for tag in tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+        conn.execute("INSERT OR IGNORE INTO SheetTag (sheetId, tag) VALUES (?1, ?2)", params![id, tag])?;
+    }
+
+    Ok(())
+}
+
+pub fn get_sheet(conn: &Connection, id: i64) -> Result<Sheet, DatabaseError> {
+    let sheet = conn.query_row("SELECT * FROM Sheet WHERE id = ?1", params![id], sheet_from_row)
+        .optional()?
+        .ok_or(DatabaseError::NotFound(format!("Sheet {}", id)))?;
+
+    Ok(Sheet { tags: sheet_tags(conn, id)?, ..sheet })
+}
+
+// inserts or updates the sheet and its tags, for callers already inside a transaction
+pub fn write_sheet(tx: &Connection, sheet: &Sheet) -> Result<i64, DatabaseError> {
+    validate(sheet)?;
+
+    let transposes = serde_json::to_string(&sheet.transposes).unwrap();
+    let annotations = serde_json::to_string(&sheet.annotations).unwrap();
+
+    let id = match sheet.id {
+        Some(id) => {
+            let updated = tx.execute(
+                "UPDATE Sheet SET title = ?1, artist = ?2, content = ?3, imagePath = ?4, transposes = ?5, transposeAnnotations = ?6 WHERE id = ?7",
+                params![sheet.title, sheet.artist, sheet.content, sheet.image_path, transposes, annotations, id],
+            )?;
+
+            if updated == 0 {
+                return Err(DatabaseError::NotFound(format!("Sheet {}", id)));
+            }
+
+            id
+        }
+        None => {
+            tx.execute(
+                "INSERT INTO Sheet (title, artist, content, imagePath, transposes, transposeAnnotations, createdAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![sheet.title, sheet.artist, sheet.content, sheet.image_path, transposes, annotations, unix_timestamp()],
+            )?;
+
+            tx.last_insert_rowid()
+        }
+    };
+
+    set_sheet_tags(tx, id, &sheet.tags)?;
+
+    Ok(id)
+}
+
+pub fn put_sheet(conn: &mut Connection, sheet: Sheet) -> Result<Sheet, DatabaseError> {
+    let tx = conn.transaction()?;
+    let id = write_sheet(&tx, &sheet)?;
+    tx.commit()?;
+
+    get_sheet(conn, id)
+}
+
+// newest played first, sheets never played after those by title
+pub fn find_sheets(conn: &Connection, tag: Option<&str>, search: Option<&str>) -> Result<Vec<Sheet>, DatabaseError> {
+    let search = search.map(|search| format!("%{}%", search.trim()));
+
+    let mut statement = conn.prepare(
+        "SELECT * FROM Sheet
+         WHERE (?1 IS NULL OR id IN (SELECT sheetId FROM SheetTag WHERE tag = ?1))
+           AND (?2 IS NULL OR title LIKE ?2 OR artist LIKE ?2)
+         ORDER BY lastPlayedAt IS NULL, lastPlayedAt DESC, title COLLATE NOCASE"
+    )?;
+    let sheets = statement
+        .query_map(params![tag, search], sheet_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    sheets.into_iter()
+        .map(|sheet| {
+            let tags = sheet_tags(conn, sheet.id.unwrap())?;
+            Ok(Sheet { tags, ..sheet })
+        })
+        .collect()
+}
+
+fn image_data_url(path: &str) -> Option<String> {
+    let mime = match Path::new(path).extension()?.to_str()?.to_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        _ => return None,
+    };
+
+    match std::fs::read(path) {
+        Ok(bytes) => Some(format!("data:{};base64,{}", mime, base64::engine::general_purpose::STANDARD.encode(bytes))),
+        Err(err) => {
+            error!("Failed to read sheet image {}: {}", path, err);
+            None
+        }
+    }
+}
+
+// makes the sheet the one being played, and pushes it to the main window and sheet viewer
+// with transpose_game the game is transposed to the sheet's first transpose, otherwise it's assumed to be there already
+pub unsafe fn play_sheet(conn: &Connection, id: i64, transpose_game: bool, app_handle: &AppHandle) -> Result<Sheet, DatabaseError> {
+    conn.execute("UPDATE Sheet SET lastPlayedAt = ?1 WHERE id = ?2", params![unix_timestamp(), id])?;
+    let sheet = get_sheet(conn, id)?;
+
+    if transpose_game {
+        transpose(sheet.transposes.first().cloned().unwrap_or(0));
+    }
+
+    // image sheets have no text to find markers in
+    set_sheet_text(match sheet.image_path {
+        Some(_) => None,
+        None => sheet.content.as_deref(),
+    });
+    replace_transposes(sheet.transposes.clone(), sheet.annotations.clone(), app_handle);
+    info!("Loaded sheet '{}'", sheet.title);
+
+    // the viewer can't read arbitrary paths, so images are sent inline
+    let image = sheet.image_path.as_deref().and_then(image_data_url);
+
+    let json = serde_json::to_string(&json!({"sheet": sheet, "image": image})).unwrap();
+    app_handle.emit_all("sheet_loaded", Payload { message: json });
+
+    emit_current_index(0, app_handle);
+
+    Ok(sheet)
+}
+
+#[tauri::command]
+pub fn save_sheet(database: State<Database>, sheet: Sheet) -> Result<Sheet, DatabaseError> {
+    let mut conn = database.conn()?;
+
+    put_sheet(&mut conn, sheet)
+}
+
+#[tauri::command]
+pub fn get_sheet_by_id(database: State<Database>, id: i64) -> Result<Sheet, DatabaseError> {
+    let conn = database.conn()?;
+
+    get_sheet(&conn, id)
+}
+
+#[tauri::command]
+pub fn list_sheets(database: State<Database>, tag: Option<String>, search: Option<String>) -> Result<Vec<Sheet>, DatabaseError> {
+    let conn = database.conn()?;
+
+    find_sheets(&conn, tag.as_deref(), search.as_deref())
+}
+
+#[tauri::command]
+pub fn delete_sheet(database: State<Database>, id: i64) -> Result<(), DatabaseError> {
+    let conn = database.conn()?;
+    conn.execute("DELETE FROM Sheet WHERE id = ?1", params![id])?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn load_sheet(app_handle: AppHandle, database: State<Database>, id: i64) -> Result<Sheet, DatabaseError> {
+    let conn = database.conn()?;
+
+    unsafe { play_sheet(&conn, id, false, &app_handle) }
+}
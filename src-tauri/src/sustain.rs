@@ -0,0 +1,44 @@
+use tauri::{AppHandle, Manager};
+use log::info;
+use serde_json::json;
+use crate::event_processing::Payload;
+use crate::keyboard::{press_key, release_key, SUSTAIN_KEY_BIND};
+use crate::PAUSED;
+
+// the game's sustain key while we hold it down, kept so the release matches even if the bind changed since
+static mut SUSTAIN_HELD: Option<u64> = None;
+
+fn emit_sustain(app_handle: &AppHandle) {
+    let json = serde_json::to_string(&json!({"sustain": unsafe { SUSTAIN_HELD.is_some() }})).unwrap();
+    app_handle.emit_all("frontend_event", Payload { message: json });
+}
+
+// the sustain bind, first press holds the sustain key down and the next one lets it go
+pub unsafe fn toggle_sustain(app_handle: &AppHandle) {
+    if SUSTAIN_HELD.is_some() {
+        release_sustain(app_handle);
+        return;
+    }
+
+    let keycode = match SUSTAIN_KEY_BIND {
+        Some(keycode) if !PAUSED => keycode,
+        _ => return,
+    };
+
+    press_key(keycode);
+    SUSTAIN_HELD = Some(keycode);
+    info!("Sustain held");
+
+    emit_sustain(app_handle);
+}
+
+// pausing, quitting or rebinding never leaves the key stuck down in the game
+pub unsafe fn release_sustain(app_handle: &AppHandle) {
+    if let Some(keycode) = SUSTAIN_HELD {
+        SUSTAIN_HELD = None;
+        release_key(keycode);
+        info!("Sustain released");
+
+        emit_sustain(app_handle);
+    }
+}
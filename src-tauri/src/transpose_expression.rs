@@ -0,0 +1,409 @@
+use serde::Serialize;
+
+// nested repeats grow fast, "((0)x1000)x1000" shouldn't hang the app
+const MAX_EXPANDED_LENGTH: usize = 10_000;
+// same -/+50 the transpose input allows
+const MAX_TRANSPOSE: i32 = 50;
+
+#[derive(Debug, Clone, PartialEq, Serialize, thiserror::Error)]
+#[error("{message} (line {line}, column {column})")]
+#[serde(rename_all = "camelCase")]
+pub struct ExpressionError {
+    pub message: String,
+    // 1 based, counted in characters
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransposeExpression {
+    pub transposes: Vec<i32>,
+    // labels[i] is the label transposes[i] falls under
+    pub labels: Vec<Option<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    // start is where the number is, for errors while expanding
+    Transpose { value: i32, relative: bool, start: usize },
+    Label(String),
+    // start is where the "(" is, for errors while expanding
+    Group { items: Vec<Item>, count: usize, start: usize },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+    fn error_at(&self, index: usize, message: impl Into<String>) -> ExpressionError {
+        let before = &self.chars[..index.min(self.chars.len())];
+        let line_start = before.iter().rposition(|c| *c == '\n').map_or(0, |newline| newline + 1);
+
+        ExpressionError {
+            message: message.into(),
+            line: before.iter().filter(|c| **c == '\n').count() + 1,
+            column: index - line_start + 1,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).cloned()
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                '#' => {
+                    while self.peek().map_or(false, |c| c != '\n') {
+                        self.index += 1;
+                    }
+                }
+                c if c.is_whitespace() || c == ',' => self.index += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let start = self.index;
+        while self.peek().map_or(false, &predicate) {
+            self.index += 1;
+        }
+
+        self.chars[start..self.index].iter().collect()
+    }
+
+    // items until the end, or until the ")" closing the group we're in
+    fn parse_items(&mut self, in_group: bool) -> Result<Vec<Item>, ExpressionError> {
+        let mut items = vec![];
+
+        loop {
+            self.skip_separators();
+
+            let start = self.index;
+            let item = match self.peek() {
+                None if in_group => return Err(self.error_at(start, "Missing ')' to close the group")),
+                None => return Ok(items),
+                Some(')') if in_group => return Ok(items),
+                Some(')') => return Err(self.error_at(start, "')' without a '(' to close")),
+                Some('(') => self.parse_group()?,
+                Some('"') => self.parse_quoted_label()?,
+                Some(c) if c.is_ascii_digit() || c == '+' || c == '-' => self.parse_transpose()?,
+                Some(c) if c.is_alphabetic() || c == '_' => self.parse_label()?,
+                Some(c) => return Err(self.error_at(start, format!("Unexpected '{}'", c))),
+            };
+
+            items.push(item);
+        }
+    }
+
+    fn parse_group(&mut self) -> Result<Item, ExpressionError> {
+        let group_start = self.index;
+        self.index += 1;
+        let items = self.parse_items(true)?;
+        self.index += 1;
+
+        // nested groups got the same check, so one in here means a transpose
+        if !items.iter().any(|item| matches!(item, Item::Transpose { .. } | Item::Group { .. })) {
+            return Err(self.error_at(group_start, "A group needs at least one transpose"));
+        }
+
+        // ")x4", a bare ")" plays the group once
+        let count = match self.peek() {
+            Some('x') | Some('X') => {
+                self.index += 1;
+
+                let start = self.index;
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                match digits.parse::<usize>() {
+                    Ok(count) if count > 0 => count,
+                    // too many digits to count, expanding reports it as too long
+                    Err(_) if !digits.is_empty() => usize::MAX,
+                    _ => return Err(self.error_at(start, "Expected a repeat count of at least 1 after 'x'")),
+                }
+            }
+            _ => 1,
+        };
+
+        Ok(Item::Group { items, count, start: group_start })
+    }
+
+    fn parse_transpose(&mut self) -> Result<Item, ExpressionError> {
+        let start = self.index;
+
+        let sign = match self.peek() {
+            Some('-') => { self.index += 1; -1 }
+            Some('+') => { self.index += 1; 1 }
+            _ => 1,
+        };
+
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        let value = match digits.parse::<i32>() {
+            Ok(value) => sign * value,
+            Err(_) => return Err(self.error_at(start, "Expected a number")),
+        };
+
+        let relative = self.peek() == Some('r');
+        if relative {
+            self.index += 1;
+        }
+
+        // "2x" or "3abc" is a typo, not a number and a label
+        if self.peek().map_or(false, |c| c.is_alphanumeric()) {
+            return Err(self.error_at(self.index, format!("Unexpected '{}' after a number", self.peek().unwrap())));
+        }
+
+        Ok(Item::Transpose { value, relative, start })
+    }
+
+    fn expect_colon(&mut self, label_start: usize) -> Result<(), ExpressionError> {
+        self.skip_separators();
+
+        match self.peek() {
+            Some(':') => {
+                self.index += 1;
+                Ok(())
+            }
+            _ => Err(self.error_at(label_start, "Expected ':' after the label")),
+        }
+    }
+
+    fn parse_label(&mut self) -> Result<Item, ExpressionError> {
+        let start = self.index;
+        let label = self.take_while(|c| c.is_alphanumeric() || c == '_' || c == '-');
+        self.expect_colon(start)?;
+
+        Ok(Item::Label(label))
+    }
+
+    fn parse_quoted_label(&mut self) -> Result<Item, ExpressionError> {
+        let start = self.index;
+        self.index += 1;
+
+        let label = self.take_while(|c| c != '"' && c != '\n');
+        if self.peek() != Some('"') {
+            return Err(self.error_at(start, "Missing closing '\"' for the label"));
+        }
+        self.index += 1;
+        self.expect_colon(start)?;
+
+        Ok(Item::Label(label))
+    }
+}
+
+// how many transposes the items expand to, saturating instead of overflowing
+fn expanded_length(items: &[Item]) -> usize {
+    items.iter().fold(0, |length: usize, item| {
+        let item_length = match item {
+            Item::Transpose { .. } => 1,
+            Item::Label(_) => 0,
+            Item::Group { items, count, .. } => expanded_length(items).saturating_mul(*count),
+        };
+
+        length.saturating_add(item_length)
+    })
+}
+
+// errors are (index into the text, message)
+fn expand(items: &[Item], expression: &mut TransposeExpression, label: &mut Option<String>) -> Result<(), (usize, String)> {
+    for item in items {
+        match item {
+            Item::Transpose { value, relative, start } => {
+                let previous = expression.transposes.last().cloned().unwrap_or(0);
+                let transpose = match relative {
+                    true => previous.checked_add(*value),
+                    false => Some(*value),
+                };
+
+                // relative steps are checked as they land, "49 (1r)x2" only goes out on the second repeat
+                let transpose = match transpose {
+                    Some(transpose) if (-MAX_TRANSPOSE..=MAX_TRANSPOSE).contains(&transpose) => transpose,
+                    _ => return Err((*start, "Transpose out of range".to_string())),
+                };
+
+                expression.transposes.push(transpose);
+                expression.labels.push(label.clone());
+            }
+            Item::Label(new_label) => *label = Some(new_label.clone()),
+            Item::Group { items, count, start } => {
+                // checked before repeating anything, a huge count would otherwise loop for ages
+                let length = expanded_length(items).saturating_mul(*count);
+                if expression.transposes.len().saturating_add(length) > MAX_EXPANDED_LENGTH {
+                    return Err((*start, format!("Expands to more than {} transposes", MAX_EXPANDED_LENGTH)));
+                }
+
+                for _ in 0..*count {
+                    expand(items, expression, label)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// the syntax, whitespace or commas between items:
+//   0 -1 +2        transposes
+//   +2r -1r        relative to the transpose before, "two above the previous"
+//   (0 2 -1)x4     repeat a group, groups nest
+//   chorus: 0 3    label the transposes after it, "Verse 2": for labels with spaces
+//   # comment      until the end of the line
+pub fn parse_expression(text: &str) -> Result<TransposeExpression, ExpressionError> {
+    let mut parser = Parser { chars: text.chars().collect(), index: 0 };
+    let items = parser.parse_items(false)?;
+
+    let mut expression = TransposeExpression::default();
+    expand(&items, &mut expression, &mut None).map_err(|(index, message)| parser.error_at(index, message))?;
+
+    Ok(expression)
+}
+
+#[tauri::command]
+pub fn parse_transpose_expression(text: String) -> Result<TransposeExpression, ExpressionError> {
+    parse_expression(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transposes(text: &str) -> Vec<i32> {
+        parse_expression(text).unwrap().transposes
+    }
+
+    fn error(text: &str) -> ExpressionError {
+        parse_expression(text).unwrap_err()
+    }
+
+    #[test]
+    fn plain_transposes() {
+        assert_eq!(transposes("0 -1 +2"), vec![0, -1, 2]);
+        assert_eq!(transposes("0,-1,\n+2"), vec![0, -1, 2]);
+        assert_eq!(transposes(""), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        assert_eq!(transposes("0 # intro\n3 # 4 5"), vec![0, 3]);
+    }
+
+    #[test]
+    fn groups_repeat() {
+        assert_eq!(transposes("(0 2)x3"), vec![0, 2, 0, 2, 0, 2]);
+        assert_eq!(transposes("(1)"), vec![1]);
+        assert_eq!(transposes("(1)X2"), vec![1, 1]);
+    }
+
+    #[test]
+    fn groups_nest() {
+        assert_eq!(transposes("((0 1)x2 5)x2"), vec![0, 1, 0, 1, 5, 0, 1, 0, 1, 5]);
+        assert_eq!(transposes("(((7)))"), vec![7]);
+    }
+
+    #[test]
+    fn relative_transposes_follow_the_previous() {
+        assert_eq!(transposes("2 +2r -1r"), vec![2, 4, 3]);
+        // nothing before counts as 0
+        assert_eq!(transposes("3r"), vec![3]);
+        // each repeat steps on from where the last one ended
+        assert_eq!(transposes("0 (1r)x3"), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn labels_apply_to_the_transposes_after_them() {
+        let expression = parse_expression("0 chorus: 3 4 \"Verse 2\": -1").unwrap();
+
+        assert_eq!(expression.transposes, vec![0, 3, 4, -1]);
+        assert_eq!(expression.labels, vec![
+            None,
+            Some("chorus".to_string()),
+            Some("chorus".to_string()),
+            Some("Verse 2".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn labels_inside_groups_carry_on_after_them() {
+        let expression = parse_expression("(a: 1 b: 2)x2 3").unwrap();
+
+        assert_eq!(expression.transposes, vec![1, 2, 1, 2, 3]);
+        assert_eq!(expression.labels.iter().map(|label| label.as_deref()).collect::<Vec<_>>(), vec![
+            Some("a"), Some("b"), Some("a"), Some("b"), Some("b"),
+        ]);
+    }
+
+    #[test]
+    fn errors_point_at_line_and_column() {
+        assert_eq!(error("0 1 ?"), ExpressionError { message: "Unexpected '?'".to_string(), line: 1, column: 5 });
+
+        // where the ")" should have been
+        let err = error("0\n  (1 2");
+        assert_eq!((err.line, err.column), (2, 7));
+        assert_eq!(err.message, "Missing ')' to close the group");
+
+        let err = error("0 1)");
+        assert_eq!((err.line, err.column), (1, 4));
+
+        let err = error("2x");
+        assert_eq!((err.line, err.column), (1, 2));
+
+        let err = error("chorus 3");
+        assert_eq!((err.line, err.column), (1, 1));
+
+        let err = error("\"Verse 2: 3");
+        assert_eq!((err.line, err.column), (1, 1));
+    }
+
+    #[test]
+    fn repeat_counts_must_be_at_least_one() {
+        let err = error("(1)x0");
+        assert_eq!((err.line, err.column), (1, 5));
+
+        assert!(parse_expression("(1)x").is_err());
+    }
+
+    #[test]
+    fn empty_and_label_only_groups_are_rejected() {
+        let err = error("0 ()x99999999999");
+        assert_eq!((err.line, err.column), (1, 3));
+        assert_eq!(err.message, "A group needs at least one transpose");
+
+        assert!(parse_expression("(intro:)x18446744073709551615").is_err());
+        assert!(parse_expression("(())").is_err());
+    }
+
+    #[test]
+    fn expansion_is_limited() {
+        assert_eq!(transposes("(0)x10000").len(), MAX_EXPANDED_LENGTH);
+
+        let err = error("(0)x10001");
+        assert_eq!((err.line, err.column), (1, 1));
+        assert!(err.message.starts_with("Expands to more than"));
+
+        // found before any repeating, however big the count
+        assert!(parse_expression("((0)x1000)x1000").is_err());
+        assert!(parse_expression("(0 1)x18446744073709551615").is_err());
+        assert!(parse_expression("(0)x99999999999999999999999").is_err());
+        assert!(parse_expression("1 2 (0)x9999").is_err());
+    }
+
+    #[test]
+    fn transposes_stay_within_range() {
+        assert_eq!(transposes("-50 50 -1r"), vec![-50, 50, 49]);
+
+        let err = error("0 51");
+        assert_eq!((err.line, err.column), (1, 3));
+        assert_eq!(err.message, "Transpose out of range");
+
+        // overflowing i32 is out of range too, not a panic
+        let err = error("50 2147483647r");
+        assert_eq!((err.line, err.column), (1, 4));
+        assert!(parse_expression("-50 -2147483647r").is_err());
+
+        let err = error("49 (1r)x2");
+        assert_eq!((err.line, err.column), (1, 5));
+    }
+}
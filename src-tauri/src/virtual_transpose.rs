@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Sender};
+use std::time::{Duration, Instant};
+use lazy_static::lazy_static;
+use log::{error, info};
+use rdev::{grab, simulate, Event, EventType, Key};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::autoplay::key_for_layout_key;
+use crate::keyboard::KEY_LISTEN;
+use crate::layout::{Keystroke, Layout, Modifier};
+use crate::{CURRENT_TRANSPOSE, PAUSED};
+
+// a simulated event that hasn't come back through grab by then never will, some platforms simulate around the hook
+const PENDING_TIMEOUT: Duration = Duration::from_millis(100);
+// most events waiting to come back, the oldest go first past this
+const MAX_PENDING: usize = 32;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutOfRange {
+    // the key does nothing
+    #[default]
+    Drop,
+    // the lowest or highest key plays instead
+    Clamp,
+}
+
+// transposing remaps note keys instead of pressing the game's transpose keys, from settings
+pub static mut VIRTUAL_TRANSPOSE: bool = false;
+pub static mut OUT_OF_RANGE: OutOfRange = OutOfRange::Drop;
+
+// one of our windows has focus, keys typed into the app aren't notes
+pub static mut APP_FOCUSED: bool = false;
+
+static mut GRAB_STARTED: bool = false;
+// the modifier keys the user holds, not counting the ones we send ourselves
+static mut SHIFT_HELD: Option<Key> = None;
+static mut CTRL_HELD: Option<Key> = None;
+
+lazy_static! {
+    // from settings, which key plays which pitch
+    static ref LAYOUT: Mutex<Layout> = Mutex::new(Layout::vp61());
+    // physical key -> key sent for it, None if it was dropped, so its release matches even if the transpose changed
+    static ref HELD_NOTES: Mutex<HashMap<Key, Option<Key>>> = Mutex::new(HashMap::new());
+    // events we simulated and when, grab lets them through untouched when they come back around
+    static ref PENDING: Mutex<Vec<(EventType, Instant)>> = Mutex::new(vec![]);
+    static ref SENDER: Mutex<Option<Sender<Vec<EventType>>>> = Mutex::new(None);
+}
+
+pub fn set_layout(layout: Layout) {
+    let (lowest_octave, highest_octave) = layout.octave_bounds();
+    info!("Virtual transposing on the {} layout, octaves {} to {}", layout.name, lowest_octave, highest_octave);
+
+    *LAYOUT.lock().unwrap() = layout;
+}
+
+// the character a letter or digit key types unshifted on a us layout, None for every other key
+fn char_for_key(key: Key) -> Option<char> {
+    let name = match serde_json::to_value(key).ok()? {
+        Value::String(name) => name,
+        _ => return None,
+    };
+
+    match (name.strip_prefix("Key"), name.strip_prefix("Num")) {
+        (Some(letter), _) if letter.len() == 1 => Some(letter.chars().next()?.to_ascii_lowercase()),
+        (_, Some(digit)) if digit.len() == 1 => digit.chars().next(),
+        _ => None,
+    }
+}
+
+// the keystroke a key and the held modifiers make, ctrl wins over shift
+fn keystroke_for_key(key: Key, shift_held: bool, ctrl_held: bool) -> Option<Keystroke> {
+    let modifier = match (ctrl_held, shift_held) {
+        (true, _) => Modifier::Ctrl,
+        (false, true) => Modifier::Shift,
+        (false, false) => Modifier::None,
+    };
+
+    Some(Keystroke::new(char_for_key(key)?, modifier))
+}
+
+fn push_pending(pending: &mut Vec<(EventType, Instant)>, event_type: EventType, now: Instant) {
+    if pending.len() == MAX_PENDING {
+        pending.remove(0);
+    }
+    pending.push((event_type, now));
+}
+
+// expired events are dropped first, so a real press of a key we once sent isn't taken for ours
+fn take_pending(pending: &mut Vec<(EventType, Instant)>, event_type: &EventType, now: Instant) -> bool {
+    pending.retain(|(_, sent_at)| now.duration_since(*sent_at) < PENDING_TIMEOUT);
+
+    match pending.iter().position(|(pending, _)| pending == event_type) {
+        Some(index) => {
+            pending.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+// simulating from inside the hook stalls it on some platforms, a worker sends the keys instead
+fn send(events: Vec<EventType>) {
+    if let Some(sender) = SENDER.lock().unwrap().as_ref() {
+        let _ = sender.send(events);
+    }
+}
+
+// events for pressing the key that's `transpose` semitones away, None when the key isn't a note of the layout
+// shift_held and ctrl_held are the modifier keys the user holds, let go around the note and put back after
+fn remap_press(
+    layout: &Layout,
+    key: Key,
+    transpose: i32,
+    out_of_range: OutOfRange,
+    shift_held: Option<Key>,
+    ctrl_held: Option<Key>,
+) -> Option<(Vec<EventType>, Option<Key>)> {
+    let pitch = layout.pitch_for_keystroke(keystroke_for_key(key, shift_held.is_some(), ctrl_held.is_some())?)?;
+    let mut target = pitch + transpose;
+
+    if !layout.contains(target) {
+        match out_of_range {
+            OutOfRange::Drop => return Some((vec![], None)),
+            OutOfRange::Clamp => target = target.clamp(layout.lowest_pitch, layout.highest_pitch()),
+        }
+    }
+
+    let keystroke = layout.keystroke_for_pitch(target)?;
+    let target_key = key_for_layout_key(keystroke.key)?;
+
+    // modifiers only change for the one key, whatever the user is holding is put back straight after
+    let (wanted, held) = match keystroke.modifier {
+        Modifier::None => (None, [shift_held, ctrl_held]),
+        Modifier::Shift => (Some(Key::ShiftLeft).filter(|_| shift_held.is_none()), [ctrl_held, None]),
+        Modifier::Ctrl => (Some(Key::ControlLeft).filter(|_| ctrl_held.is_none()), [shift_held, None]),
+    };
+    let held: Vec<Key> = held.into_iter().flatten().collect();
+
+    let mut events: Vec<EventType> = held.iter().map(|key| EventType::KeyRelease(*key)).collect();
+    events.extend(wanted.map(EventType::KeyPress));
+    events.push(EventType::KeyPress(target_key));
+    events.extend(wanted.map(EventType::KeyRelease));
+    events.extend(held.iter().map(|key| EventType::KeyPress(*key)));
+
+    Some((events, Some(target_key)))
+}
+
+// typing into our own windows or binding a key isn't playing
+unsafe fn is_remapping() -> bool {
+    VIRTUAL_TRANSPOSE && !PAUSED && !KEY_LISTEN && !APP_FOCUSED
+}
+
+unsafe fn grab_callback(event: Event) -> Option<Event> {
+    if take_pending(&mut PENDING.lock().unwrap(), &event.event_type, Instant::now()) {
+        return Some(event);
+    }
+
+    match event.event_type {
+        EventType::KeyPress(shift_key @ (Key::ShiftLeft | Key::ShiftRight)) => SHIFT_HELD = Some(shift_key),
+        EventType::KeyRelease(Key::ShiftLeft | Key::ShiftRight) => SHIFT_HELD = None,
+        EventType::KeyPress(ctrl_key @ (Key::ControlLeft | Key::ControlRight)) => CTRL_HELD = Some(ctrl_key),
+        EventType::KeyRelease(Key::ControlLeft | Key::ControlRight) => CTRL_HELD = None,
+        EventType::KeyPress(key) if is_remapping() => {
+            let remapped = remap_press(&LAYOUT.lock().unwrap(), key, CURRENT_TRANSPOSE, OUT_OF_RANGE, SHIFT_HELD, CTRL_HELD);
+
+            if let Some((events, sent_key)) = remapped {
+                HELD_NOTES.lock().unwrap().insert(key, sent_key);
+                send(events);
+                return None;
+            }
+        }
+        EventType::KeyRelease(key) => {
+            // released the same way it was pressed, even if virtual transposing was turned off since
+            if let Some(sent_key) = HELD_NOTES.lock().unwrap().remove(&key) {
+                if let Some(sent_key) = sent_key {
+                    send(vec![EventType::KeyRelease(sent_key)]);
+                }
+                return None;
+            }
+        }
+        _ => {}
+    }
+
+    Some(event)
+}
+
+// grab can't be stopped once running, so it starts the first time virtual transposing is turned on and stays
+pub unsafe fn start_grab() {
+    if GRAB_STARTED {
+        return;
+    }
+    GRAB_STARTED = true;
+
+    let (sender, receiver) = channel::<Vec<EventType>>();
+    *SENDER.lock().unwrap() = Some(sender);
+
+    std::thread::spawn(move || {
+        for events in receiver {
+            for event_type in events {
+                push_pending(&mut PENDING.lock().unwrap(), event_type, Instant::now());
+
+                if let Err(err) = simulate(&event_type) {
+                    take_pending(&mut PENDING.lock().unwrap(), &event_type, Instant::now());
+                    error!("Failed to send {:?}: {:?}", event_type, err);
+                }
+            }
+        }
+    });
+
+    std::thread::spawn(|| {
+        info!("Grabbing note keys for virtual transposing");
+
+        if let Err(err) = grab(|event| unsafe { grab_callback(event) }) {
+            // usually missing permissions, transposing goes back to the game's transpose keys
+            error!("Failed to grab keys, virtual transposing is off: {:?}", err);
+            VIRTUAL_TRANSPOSE = false;
+            GRAB_STARTED = false;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use EventType::{KeyPress, KeyRelease};
+
+    #[test]
+    fn only_letters_and_digits_have_chars() {
+        assert_eq!(char_for_key(Key::KeyQ), Some('q'));
+        assert_eq!(char_for_key(Key::Num1), Some('1'));
+        assert_eq!(char_for_key(Key::Space), None);
+        assert_eq!(char_for_key(Key::F1), None);
+        assert_eq!(char_for_key(Key::Kp1), None);
+        assert_eq!(char_for_key(Key::Unknown(1000)), None);
+    }
+
+    #[test]
+    fn ctrl_wins_over_shift() {
+        assert_eq!(keystroke_for_key(Key::KeyT, false, false), Some(Keystroke::new('t', Modifier::None)));
+        assert_eq!(keystroke_for_key(Key::KeyT, true, false), Some(Keystroke::new('t', Modifier::Shift)));
+        assert_eq!(keystroke_for_key(Key::KeyT, true, true), Some(Keystroke::new('t', Modifier::Ctrl)));
+        assert_eq!(keystroke_for_key(Key::Space, true, false), None);
+    }
+
+    #[test]
+    fn white_key_to_white_key() {
+        // t is C4, y is D4
+        let remapped = remap_press(&Layout::vp61(), Key::KeyT, 2, OutOfRange::Drop, None, None);
+
+        assert_eq!(remapped, Some((vec![KeyPress(Key::KeyY)], Some(Key::KeyY))));
+    }
+
+    #[test]
+    fn shift_is_only_held_for_a_black_key() {
+        let remapped = remap_press(&Layout::vp61(), Key::KeyT, 1, OutOfRange::Drop, None, None);
+
+        assert_eq!(remapped, Some((
+            vec![KeyPress(Key::ShiftLeft), KeyPress(Key::KeyT), KeyRelease(Key::ShiftLeft)],
+            Some(Key::KeyT),
+        )));
+    }
+
+    #[test]
+    fn held_shift_is_let_go_for_a_white_key_and_put_back() {
+        // T is C#4, a semitone down is t
+        let remapped = remap_press(&Layout::vp61(), Key::KeyT, -1, OutOfRange::Drop, Some(Key::ShiftRight), None);
+
+        assert_eq!(remapped, Some((
+            vec![KeyRelease(Key::ShiftRight), KeyPress(Key::KeyT), KeyPress(Key::ShiftRight)],
+            Some(Key::KeyT),
+        )));
+    }
+
+    #[test]
+    fn held_shift_stays_for_a_black_key() {
+        // T to Y, C#4 to D#4
+        let remapped = remap_press(&Layout::vp61(), Key::KeyT, 2, OutOfRange::Drop, Some(Key::ShiftLeft), None);
+
+        assert_eq!(remapped, Some((vec![KeyPress(Key::KeyY)], Some(Key::KeyY))));
+    }
+
+    #[test]
+    fn out_of_range_drops_or_clamps() {
+        // m is the highest key
+        let dropped = remap_press(&Layout::vp61(), Key::KeyM, 1, OutOfRange::Drop, None, None);
+        let clamped = remap_press(&Layout::vp61(), Key::KeyM, 1, OutOfRange::Clamp, None, None);
+
+        assert_eq!(dropped, Some((vec![], None)));
+        assert_eq!(clamped, Some((vec![KeyPress(Key::KeyM)], Some(Key::KeyM))));
+    }
+
+    #[test]
+    fn keys_that_arent_notes_pass_through() {
+        assert_eq!(remap_press(&Layout::vp61(), Key::Space, 2, OutOfRange::Drop, None, None), None);
+        // vp61 has no ctrl keys
+        assert_eq!(remap_press(&Layout::vp61(), Key::KeyT, 2, OutOfRange::Drop, None, Some(Key::ControlLeft)), None);
+    }
+
+    #[test]
+    fn pending_events_come_back_once() {
+        let now = Instant::now();
+        let mut pending = vec![];
+        push_pending(&mut pending, KeyPress(Key::KeyY), now);
+
+        assert!(take_pending(&mut pending, &KeyPress(Key::KeyY), now));
+        assert!(!take_pending(&mut pending, &KeyPress(Key::KeyY), now));
+    }
+
+    #[test]
+    fn pending_events_expire() {
+        let now = Instant::now();
+        let mut pending = vec![];
+        push_pending(&mut pending, KeyPress(Key::KeyY), now);
+
+        // never came back, so the user's own press of y is a real one
+        assert!(!take_pending(&mut pending, &KeyPress(Key::KeyY), now + PENDING_TIMEOUT));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn pending_events_are_capped() {
+        let now = Instant::now();
+        let mut pending = vec![];
+        push_pending(&mut pending, KeyPress(Key::KeyY), now);
+        for _ in 0..MAX_PENDING {
+            push_pending(&mut pending, KeyPress(Key::KeyU), now);
+        }
+
+        assert_eq!(pending.len(), MAX_PENDING);
+        assert!(!take_pending(&mut pending, &KeyPress(Key::KeyY), now));
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, SendError, Sender};
+use lazy_static::lazy_static;
+use crate::autoplay::play_keys;
+use crate::macros::{play_macro, KeyMacro};
+use crate::{send_transpose, CURRENT_TRANSPOSE};
+
+// everything that presses keys in the game, done one after another so nothing interleaves
+enum Job {
+    // done hears back once the keys are sent, for callers timing what comes after
+    Transpose { transpose: i32, done: Option<Sender<()>> },
+    // the game is already at this transpose, e.g. a new list starts where the game is, nothing is pressed
+    Resync(i32),
+    Macro(KeyMacro),
+    // one autoplay step, pressed together
+    Keys(Vec<char>),
+}
+
+lazy_static! {
+    static ref WORKER: Mutex<Option<Sender<Job>>> = Mutex::new(None);
+}
+
+fn spawn_worker() -> Sender<Job> {
+    let (sender, receiver) = channel::<Job>();
+
+    std::thread::spawn(move || {
+        for job in receiver {
+            match job {
+                Job::Transpose { transpose, done } => {
+                    unsafe { send_transpose(transpose); }
+
+                    if let Some(done) = done {
+                        let _ = done.send(());
+                    }
+                }
+                Job::Resync(transpose) => unsafe { CURRENT_TRANSPOSE = transpose },
+                Job::Macro(key_macro) => unsafe { play_macro(&key_macro) },
+                Job::Keys(keys) => play_keys(&keys),
+            }
+        }
+    });
+
+    sender
+}
+
+// the worker thread starts with the first job, and again if a panic took it down
+fn queue(job: Job) {
+    let mut worker = WORKER.lock().unwrap();
+
+    let job = match worker.as_ref() {
+        Some(sender) => match sender.send(job) {
+            Ok(()) => return,
+            Err(SendError(job)) => job,
+        },
+        None => job,
+    };
+
+    let sender = spawn_worker();
+    let _ = sender.send(job);
+    *worker = Some(sender);
+}
+
+// returns straight away, the key listener must never wait on keys being sent
+pub fn queue_transpose(transpose: i32) {
+    queue(Job::Transpose { transpose, done: None });
+}
+
+// for the schedule and autoplay threads, which time their next step from when the transpose is done
+pub fn transpose_and_wait(transpose: i32) {
+    let (done, finished) = channel();
+    queue(Job::Transpose { transpose, done: Some(done) });
+
+    let _ = finished.recv();
+}
+
+// after the transposes already queued, so they don't move the game away from it again
+pub fn queue_resync(transpose: i32) {
+    queue(Job::Resync(transpose));
+}
+
+pub fn queue_macro(key_macro: KeyMacro) {
+    queue(Job::Macro(key_macro));
+}
+
+// doesn't wait, autoplay times its steps from when they were due not from when the keys went out
+pub fn queue_keys(keys: Vec<char>) {
+    queue(Job::Keys(keys));
+}
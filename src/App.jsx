@@ -3,6 +3,7 @@ import "./App.css";
 import {WebviewWindow} from "@tauri-apps/api/window";
 import {useContext, useEffect, useRef, useState} from "react";
 import {listen, emit, TauriEvent} from "@tauri-apps/api/event";
+import {invoke} from "@tauri-apps/api";
 import {
   Button,
   Card, Divider,
@@ -19,6 +20,8 @@ import {useDatabase} from "./components/DatabaseProvider.jsx";
 import TransposeMatrix from "./components/TransposeMatrix.jsx";
 import {
   generalAppToastConfig,
+  getAppDataSettings,
+  migrateLocalStorageSettings,
   modOrDefault,
   onLinkClick,
   overlayToasterDefaultProps,
@@ -26,7 +29,9 @@ import {
   preventDefaultEventCallback,
   preventRefreshOnKeydownCallback,
   spawnWindow,
-  toastOnPause
+  toastOnPause,
+  toTransposeEntries,
+  writeAppDataSettings
 } from "./utils.js";
 import Volume from "./components/Volume.jsx";
 import TransposeInput from "./components/TransposeInput.jsx";
@@ -40,11 +45,13 @@ function App() {
   const [paused, setIsPaused] = useState(true);
   const transposesInputRef = useRef()
   const [transposes, setTransposes] = useState([]);
+  const [annotations, setAnnotations] = useState([]);
+  const [practiceLoop, setPracticeLoop] = useState(null);
   const [canTranspose, setCanTranspose] = useState(false);
   const [selectedIndex, setSelectedIndex] = useState(0);
   const [transposeMonitorWebview, setTransposeMonitorWebview] = useState(null)
   const [sheetViewerWebview, setSheetViewerWebview] = useState(null)
-  const [scrollVal, setScrollVal] = useState(Number(window.localStorage.getItem("scrollDownVal")) ?? 0)
+  const [scrollVal, setScrollVal] = useState(null)
 
   const [eventFromBackend, setEventFromBackend] = useState({}) // for debugging
 
@@ -85,7 +92,7 @@ function App() {
   }
 
   const getRequiredDataForExternalWindows = () => {
-    return {keybindConfig, canTranspose, transposes, selectedIndex, paused}
+    return {keybindConfig, canTranspose, transposes, annotations, practiceLoop, selectedIndex, paused}
   }
 
   const sendEventToExternalWindows = (event) => {
@@ -122,16 +129,77 @@ function App() {
             });
           }
         }
+        else if (json?.practice_loop !== undefined) {
+          setPracticeLoop(json.practice_loop)
+        }
+        else if (json?.profile_imported) {
+          // keybinds and settings were replaced underneath us, start fresh from the backend's state
+          window.location.reload()
+        }
+        else if (json?.audio_error !== undefined) {
+          appToaster.then(toaster => {
+            toaster.show({
+              ...generalAppToastConfig,
+              message: "Some sounds could not be loaded, your install may be broken. Using fallback beeps.",
+              icon: "volume-off",
+              intent: "warning",
+              timeout: 5000,
+              isCloseButtonShown: true
+            })
+          })
+        }
       }
       catch (ignored) {}
 
       setEventFromBackend(event)
     })
 
+    const unlistenSheetLoaded = listen("sheet_loaded", (event) => {
+        // the backend has already switched to this sheet's transposes
+        const {sheet} = JSON.parse(event.payload.message)
+        transposesInputRef.current.value = sheet.transposes.join(" ")
+        setTransposes(sheet.transposes)
+        setAnnotations(sheet.annotations ?? [])
+    })
+
     const unlistenSheetViewer = listen("sheet-viewer", (event) => {
         transposesInputRef.current.value = event.payload.transposes.join(" ")
-        emit("backend_event", event.payload)
+        emit("backend_event", {transposes: toTransposeEntries(event.payload.transposes, event.payload.annotations)})
         setTransposes(event.payload.transposes)
+        setAnnotations(event.payload.annotations ?? [])
+    })
+
+    // a midi or musicxml file dropped on the window replaces the transposes, a midi's times go along for the schedule
+    const unlistenFileDrop = listen(TauriEvent.WINDOW_FILE_DROP, async (event) => {
+        const path = event.payload?.[0]
+        const extension = path?.split(".").pop().toLowerCase()
+        let imported;
+
+        try {
+          if (["mid", "midi"].includes(extension)) {
+            const midi = await invoke("import_midi", {path})
+            imported = {transposes: midi.transposes, annotations: midi.times.map(time => ({label: null, note: null, time}))}
+          }
+          else if (["musicxml", "xml", "mxl"].includes(extension)) {
+            const musicxml = await invoke("import_musicxml", {path})
+            const labels = musicxml.plan.segments.map(segment => `m. ${musicxml.measures[segment.start]}`)
+            imported = {transposes: musicxml.transposes, annotations: labels.map(label => ({label, note: null}))}
+          }
+          else {
+            return
+          }
+        }
+        catch (err) {
+          appToaster.then(toaster => {
+            toaster.show({...generalAppToastConfig, message: `${err}`, icon: "import", intent: "danger", timeout: 3000, isCloseButtonShown: true})
+          })
+          return
+        }
+
+        transposesInputRef.current.value = imported.transposes.join(" ")
+        emit("backend_event", {transposes: toTransposeEntries(imported.transposes, imported.annotations)})
+        setTransposes(imported.transposes)
+        setAnnotations(imported.annotations)
     })
 
     // prevents window refresh
@@ -144,6 +212,8 @@ function App() {
     return () => {
       unlisten.then((cleanFn) => cleanFn());
       unlistenSheetViewer.then((cleanFn) => cleanFn());
+      unlistenSheetLoaded.then((cleanFn) => cleanFn());
+      unlistenFileDrop.then((cleanFn) => cleanFn());
       removeEventListener('keydown', preventRefreshOnKeydownCallback);
       removeEventListener('contextmenu', preventDefaultEventCallback);
       removeEventListener('keydown', preventCaretOnKeydownCallback);
@@ -182,17 +252,30 @@ function App() {
 
   useEffect(() => {
     sendEventToExternalWindows(getRequiredDataForExternalWindows())
-  }, [keybindConfig, canTranspose, transposes, selectedIndex, paused]);
+  }, [keybindConfig, canTranspose, transposes, annotations, practiceLoop, selectedIndex, paused]);
 
   useEffect(() => {
-    window.localStorage.setItem("scrollDownVal", scrollVal)
-    emit("backend_event", {scroll_value: scrollVal})
+    // the backend drops the loop whenever the transposes are replaced
+    setPracticeLoop(null)
+  }, [transposes]);
+
+  useEffect(() => {
+    migrateLocalStorageSettings().then(getAppDataSettings).then(settings => setScrollVal(settings.scroll.value))
+  }, [])
+
+  useEffect(() => {
+    if (scrollVal !== null) writeAppDataSettings({scroll: {value: scrollVal}})
   }, [scrollVal])
 
+  const hasScrollBind = ["scroll_down", "scroll_up", "auto_scroll"].some(name => keybindConfig.config?.[name]?.value != null)
+
   return (
     isDatabaseReady &&
       <div className={"container"}>
-        <TransposeInput ref={transposesInputRef} toaster={appToaster} canTranspose={canTranspose} onUpdate={setTransposes}/>
+        <TransposeInput ref={transposesInputRef} toaster={appToaster} canTranspose={canTranspose} onUpdate={(transposes, annotations) => {
+          setTransposes(transposes)
+          setAnnotations(annotations)
+        }}/>
 
         <div style={{display: "flex", gap: 10, justifyContent: "center"}}>
           <span style={{display: "flex", alignItems: "center"}}>Tools</span>
@@ -238,12 +321,12 @@ function App() {
                   <Icon icon={"sort"} size={IconSize.LARGE} color={"gray"}/>
                   <p style={{color: "gray", marginTop: "auto", marginBottom: "auto"}}>Scroll</p>
                   <NumericInput
-                      disabled={keybindConfig.config?.scroll_down?.value == null}
+                      disabled={!hasScrollBind}
                       inputClassName={"scroll-amount"}
                       buttonPosition={"left"}
                       placeholder={"Scroll"}
                       onValueChange={(valAsNum, valAsString, el) => setScrollVal(~~valAsNum)}
-                      value={keybindConfig.config?.scroll_down?.value == null ? null : scrollVal}
+                      value={hasScrollBind ? scrollVal : null}
                       min={0}
                       max={200}
                   />
@@ -252,7 +335,7 @@ function App() {
             </div>
           </div>
 
-          <TransposeMatrix index={selectedIndex} transposes={transposes}/>
+          <TransposeMatrix index={selectedIndex} transposes={transposes} annotations={annotations} practiceLoop={practiceLoop}/>
         </span>
 
         <span className={"version"}>
@@ -1,11 +1,12 @@
 import {useEffect, useState} from "react";
 import {emit, listen} from "@tauri-apps/api/event";
 import {
+    generalAppToastConfig,
     overlayToasterDefaultProps, preventCaretOnKeydownCallback, preventDefaultEventCallback,
     preventRefreshOnKeydownCallback,
     toastOnPause
 } from "./utils.js";
-import {Callout, Icon, IconSize, OverlayToaster, Spinner, Tag, Tooltip} from "@blueprintjs/core";
+import {Callout, Icon, IconSize, NumericInput, OverlayToaster, Spinner, Tag, Tooltip} from "@blueprintjs/core";
 import TransposeMonitor from "./components/TransposeMonitor.jsx";
 import SheetViewerSettings from "./components/SheetViewerSettings.jsx";
 import {invoke} from "@tauri-apps/api";
@@ -31,6 +32,9 @@ function SheetViewer() {
     const [isSettingsOpen, setIsSettingsOpen] = useState(false);
     const [filePath, setFilePath] = useState("")
     const [content, setContent] = useState()
+    const [sheetPosition, setSheetPosition] = useState(null)
+    const [autoplayState, setAutoplayState] = useState("stopped")
+    const [autoplayBpm, setAutoplayBpm] = useState(120)
     const [isContentHidden, setIsContentHidden] = useState(false)
     const [isTransposesInputHidden, setIsTransposesInputHidden] = useState(true)
     const [zoomLevel, setZoomLevel] = useState(0.1);
@@ -240,6 +244,36 @@ function SheetViewer() {
             setData(event.payload)
         })
 
+        // the backend knows which marker in the sheet the current transpose belongs to
+        const unlistenFrontendEvent = listen("frontend_event", (event) => {
+            const json = JSON.parse(event.payload.message)
+
+            if (json?.current_index !== undefined) {
+                setSheetPosition(json.sheet_position ?? null)
+            }
+
+            if (json?.autoplay) {
+                setAutoplayState(json.autoplay.state)
+            }
+        })
+
+        const unlistenSheetLoaded = listen("sheet_loaded", (event) => {
+            const {sheet, image} = JSON.parse(event.payload.message)
+
+            if (image) {
+                setContent(image)
+                setFilePath("pasted_image")
+            }
+            else {
+                setContent(sheet.content ?? "")
+                setFilePath("text")
+            }
+
+            setLoading(false)
+            setZoomLevel(0.2)
+            window.scroll(0, 0)
+        })
+
         // const customMaximize = async () => {
         //     try {
         //         await appWindow.maximize();
@@ -289,6 +323,8 @@ function SheetViewer() {
 
         return () => {
             unlisten.then(cleanFn => cleanFn());
+            unlistenSheetLoaded.then(cleanFn => cleanFn());
+            unlistenFrontendEvent.then(cleanFn => cleanFn());
             unlistenResize.then(cleanFn => cleanFn())
             removeEventListener("dragover", handleDragover)
             removeEventListener("dragleave", handleDragleave)
@@ -342,6 +378,56 @@ function SheetViewer() {
         invoke("set_window_focusable", {focusable: isWindowFocused})
     }, [isWindowFocused])
 
+    useEffect(() => {
+        if (!filePath) return;
+
+        // markers are only looked for in text sheets
+        emit("backend_event", {sheet_text: isFilePathImage(filePath) ? null : content ?? ""})
+    }, [content, filePath])
+
+    useEffect(() => {
+        document.getElementById("sheet-position")?.scrollIntoView({behavior: "smooth", block: "start"})
+    }, [sheetPosition, content])
+
+    const toggleAutoplay = () => {
+        if (autoplayState === "playing" || autoplayState === "paused") {
+            invoke("stop_autoplay")
+            return
+        }
+
+        // plays once unpaused, the pause bind pauses and resumes it
+        invoke("start_autoplay", {text: content ?? "", bpm: autoplayBpm})
+            .catch((error) => {
+                toaster.then((toaster) => {
+                    toaster.clear();
+
+                    toaster.show({
+                        ...generalAppToastConfig,
+                        message: error,
+                        icon: "play",
+                        intent: "danger",
+                        timeout: 2000,
+                        isCloseButtonShown: true,
+                    });
+                });
+            })
+    }
+
+    // positions count characters, Array.from keeps characters outside the BMP whole
+    const renderTextContent = () => {
+        if (!sheetPosition) return content;
+
+        const chars = Array.from(content ?? "")
+
+        return (
+            <>
+                {chars.slice(0, sheetPosition.start).join("")}
+                <mark id={"sheet-position"}>{chars.slice(sheetPosition.start, sheetPosition.end).join("")}</mark>
+                {chars.slice(sheetPosition.end).join("")}
+            </>
+        )
+    }
+
     useEffect(() => {
         if (isInKeyboardArea) {
             invoke("set_window_focusable", {focusable: true})
@@ -437,7 +523,7 @@ function SheetViewer() {
                                                 whiteSpace: "break-spaces",
                                             }}
                                         >
-                                            {content}
+                                            {renderTextContent()}
                                       </span>
                                     )
                                 )}
@@ -453,10 +539,10 @@ function SheetViewer() {
                             mainWindowTransposes={data.transposes}
                             canTranspose={true}
                             backend={false}
-                            onUpdate={(transposes) => {
+                            onUpdate={(transposes, annotations) => {
                                 // transfer it to the main window, which will go update its transposes input, updating the backend
                                 if (JSON.stringify(data?.transposes ?? "[]") != JSON.stringify(transposes))
-                                    mainWindow.emit("sheet-viewer", {transposes})
+                                    mainWindow.emit("sheet-viewer", {transposes, annotations})
                             }}
                         />
                     </span>
@@ -496,6 +582,32 @@ function SheetViewer() {
                             </Tooltip>
                         </span>
                         <TransposeMonitor data={data}/>
+                        {sheetPosition?.measure &&
+                            <Tag minimal={true}>measure {sheetPosition.measure}</Tag>
+                        }
+                        {!isFilePathImage(filePath) &&
+                            <span style={{display: "flex", alignItems: "center", gap: 5}}>
+                                <Tooltip content={"Autoplay the sheet into the game"} compact={true} usePortal={false}>
+                                    <Icon
+                                        className={"sheet-viewer-visibility-btn"}
+                                        icon={autoplayState === "playing" || autoplayState === "paused" ? "stop" : "play"}
+                                        size={IconSize.STANDARD}
+                                        color={autoplayState === "playing" && "black"}
+                                        onClick={toggleAutoplay}
+                                    />
+                                </Tooltip>
+                                <NumericInput
+                                    id={"autoplay-bpm"}
+                                    style={{width: 50}}
+                                    buttonPosition={"none"}
+                                    onValueChange={(valAsNum) => setAutoplayBpm(valAsNum)}
+                                    value={autoplayBpm}
+                                    min={20}
+                                    max={400}
+                                    small={true}
+                                />
+                            </span>
+                        }
                         <Icon
                             className={"sheet-viewer-visibility-btn"}
                             icon={"array-numeric"}
@@ -9,7 +9,7 @@ function CurrentTransposeItem({data}) {
     return (
         transpose !== undefined
             ?
-            <TransposeMatrixItem index={data.selectedIndex} transpose={transpose} selected={true} />
+            <TransposeMatrixItem index={data.selectedIndex} transpose={transpose} label={data.annotations?.[data.selectedIndex]?.label} note={data.annotations?.[data.selectedIndex]?.note} selected={true} />
             :
             <BlankTransposeMatrixItem/>
     )
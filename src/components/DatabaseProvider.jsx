@@ -1,6 +1,5 @@
 import Database from "tauri-plugin-sql-api";
 import {createContext, useContext, useEffect, useState} from "react";
-import {initTables} from "../queries.js";
 
 const DatabaseContext = createContext(null);
 
@@ -9,12 +8,10 @@ export const DatabaseProvider = ({children}) => {
     const [database, setDatabase] = useState();
 
     useEffect(() => {
+        // tables are created by the backend migrations on startup
         Database.load("sqlite:multi_transpose.db").then((db) => {
-            // ideally, this would be done in the backend, but since its desktop and migrations aren't working, probably ok
-            db.execute(initTables).then(() => {
-                setDatabase(db);
-                setIsDatabaseReady(true);
-            })
+            setDatabase(db);
+            setIsDatabaseReady(true);
         })
     }, []);
 
@@ -1,10 +1,10 @@
 import KeyBind from "./KeyBind.jsx";
 import {emit, listen, once} from "@tauri-apps/api/event";
 import {useEffect, useState} from "react";
-import {Section, SectionCard} from "@blueprintjs/core";
-import {useDatabase} from "./DatabaseProvider.jsx";
-import {getKeybindConfig, updateKeybindConfig} from "../queries.js";
+import {Section, SectionCard, Switch} from "@blueprintjs/core";
+import {invoke} from "@tauri-apps/api";
 import {appToaster} from "../App.jsx";
+import {getAppDataSettings, writeAppDataSettings} from "../utils.js";
 
 const defaultConfig = {
     version: 3,
@@ -41,18 +41,108 @@ const defaultConfig = {
         },
         "scroll_down": {
             "purpose": "Scroll",
-            "desc": "The key you want to use to scroll down when hovering over the scroll area of your sheet.",
+            "desc": "The key you want to use to scroll down when hovering over the scroll area of your sheet. Hold it to keep scrolling.",
+            "value": null,
+            "required": false
+        },
+        "scroll_up": {
+            "purpose": "Scroll Up",
+            "desc": "The key you want to use to scroll back up when hovering over the scroll area of your sheet. Hold it to keep scrolling.",
+            "value": null,
+            "required": false
+        },
+        "auto_scroll": {
+            "purpose": "Auto Scroll",
+            "desc": "Starts or stops scrolling down on its own by the Scroll amount, for reading long sheets hands-free. Pausing holds it.",
+            "value": null,
+            "required": false
+        },
+        "auto_scroll_faster": {
+            "purpose": "Auto Scroll Faster",
+            "desc": "Makes Auto Scroll scroll more often.",
+            "value": null,
+            "required": false
+        },
+        "auto_scroll_slower": {
+            "purpose": "Auto Scroll Slower",
+            "desc": "Makes Auto Scroll scroll less often.",
+            "value": null,
+            "required": false
+        },
+        "metronome": {
+            "purpose": "Metronome",
+            "desc": "Toggles the metronome. A count-in can be configured to play before resuming with Pause All Binds.",
+            "value": null,
+            "required": false
+        },
+        "next_song": {
+            "purpose": "Next Song",
+            "desc": "Switches to the next sheet of the active setlist, transposing from the current transpose to the song's first.",
+            "value": null,
+            "required": false
+        },
+        "previous_song": {
+            "purpose": "Previous Song",
+            "desc": "Switches to the previous sheet of the active setlist.",
+            "value": null,
+            "required": false
+        },
+        "schedule": {
+            "purpose": "Start/Stop Schedule",
+            "desc": "Starts transposing on its own at each transpose's time, e.g. from a MIDI import. Pausing holds the schedule's clock.",
+            "value": null,
+            "required": false
+        },
+        "loop_start": {
+            "purpose": "Loop Start",
+            "desc": "Marks the current transpose as the start of a practice loop. Press again on the same transpose to clear the loop.",
+            "value": null,
+            "required": false
+        },
+        "loop_end": {
+            "purpose": "Loop End",
+            "desc": "Marks the current transpose as the end of a practice loop, Next Transpose then goes back to the loop start.",
+            "value": null,
+            "required": false
+        },
+        "undo_transpose": {
+            "purpose": "Undo Transpose",
+            "desc": "Goes back to the transpose before the last Next/Previous Transpose, e.g. after pressing Next by mistake.",
+            "value": null,
+            "required": false
+        },
+        "redo_transpose": {
+            "purpose": "Redo Transpose",
+            "desc": "Goes forward again to the transpose Undo Transpose went back from.",
+            "value": null,
+            "required": false
+        },
+        "sustain": {
+            "purpose": "Sustain",
+            "desc": "Holds Sustain Key down in your piano app until pressed again. Pausing releases it.",
+            "value": null,
+            "required": false
+        },
+        "sustain_key": {
+            "purpose": "Sustain Key",
+            "desc": "The key used in your piano app as the sustain pedal, e.g. Space. They must match!",
             "value": null,
             "required": false
         }
     }
 }
 
+// virtual transposing remaps note keys instead of pressing these, so they're optional while it's on, same as the backend
+const gameTransposeBinds = new Set(["transpose_up", "transpose_down"]);
+
+const isRequired = (name, data, virtualTranspose) => {
+    return (data?.required === undefined || data.required === true) && !(virtualTranspose && gameTransposeBinds.has(name))
+}
+
 const generalKeyBindToastConfig = {timeout:  3000, isCloseButtonShown: true, icon: 'key'}
 const restrictedKeys = new Set("1!2@34$5%6^78*9(0)qwertyuiopQWERTYUIOPasdfghjklASDFGHJKLzxcvbnmZXCVBNM");
 
 const KeyBindManager = ({onListen = (isListening) => {}, onKeybindSet = (e) => {}}) => {
-    const {database} = useDatabase();
     const [config, setConfig] = useState({...defaultConfig.keys});//s
     const [configName, setConfigName] = useState("default");
     const [hasFetchedDefaultConfig, setHasFetchedDefaultConfig] = useState(false);
@@ -61,6 +151,12 @@ const KeyBindManager = ({onListen = (isListening) => {}, onKeybindSet = (e) => {
     const [keysInUse, setKeysInUse] = useState(new Set());
     const [isAbleToTranspose, setIsAbleToTranspose] = useState(false);
     const [isManagerOpen, setIsManagerOpen] = useState(false);
+    const [virtualTranspose, setVirtualTranspose] = useState(false);
+
+    const virtualTransposeHandler = () => {
+        setVirtualTranspose(!virtualTranspose);
+        writeAppDataSettings({virtualTranspose: {enabled: !virtualTranspose}});
+    }
 
     const listenForKey = (name) => {
         // already listening
@@ -108,7 +204,8 @@ const KeyBindManager = ({onListen = (isListening) => {}, onKeybindSet = (e) => {
             emit("backend_event", {bind: {name: name, keycode: keycode}});
 
             // update DB with updated config
-            database.execute(updateKeybindConfig(configName, config, configName === "default"))
+            invoke("save_keybind_profile", {name: configName, keys: config, isDefault: configName === "default"})
+                .catch(err => console.error(err))
         })
     }
 
@@ -159,30 +256,31 @@ const KeyBindManager = ({onListen = (isListening) => {}, onKeybindSet = (e) => {
     }
 
     useEffect(() => {
-        // get the default config on first render
-        database.select(getKeybindConfig(configName))
-        .then((result) => {
+        getAppDataSettings().then(settings => setVirtualTranspose(settings.virtualTranspose?.enabled ?? false))
+
+        // get the default config on first render, the backend has already restored its binds on startup
+        invoke("load_keybind_profile", {name: configName})
+        .then((profile) => {
             const newKeysInUseSet = new Set();
 
-            if (result.length > 0) {
-                try {
-                    const keys = JSON.parse(result[0].json)?.keys ?? defaultConfig.keys;
-                    const prevConfig = {...defaultConfig.keys, ...keys};
-                    setConfig(prevConfig);
+            if (profile) {
+                // only the bound keys come from the profile, imported profiles carry nothing else
+                const keys = profile.keys ?? {};
+                const prevConfig = Object.fromEntries(
+                    Object.entries(defaultConfig.keys).map(([name, data]) => [name, {...data, value: keys[name]?.value ?? null}])
+                );
+                setConfig(prevConfig);
 
-                    // register keys with backend, and here as in use
-                    Object.entries(prevConfig).map(([name, value]) => {
-                        const keyObj = value.value;
+                // register keys here as in use
+                Object.values(prevConfig).map((value) => {
+                    const keyObj = value.value;
 
-                        if (keyObj?.key) {
-                            newKeysInUseSet.add(keyObj.key);
-                            emit("backend_event", {bind: {name: name, keycode: keyObj.keyCode}})
-                        }
-                    })
+                    if (keyObj?.key) {
+                        newKeysInUseSet.add(keyObj.key);
+                    }
+                })
 
-                    setKeysInUse(new Set(newKeysInUseSet));
-                }
-                catch (err) {console.error(err)}
+                setKeysInUse(new Set(newKeysInUseSet));
             }
         })
         .catch(err => console.error(err))
@@ -193,12 +291,12 @@ const KeyBindManager = ({onListen = (isListening) => {}, onKeybindSet = (e) => {
 
     useEffect(() => {
         if (hasFetchedDefaultConfig) {
-            let canTranspose = Object.values(config).every(key => !key.required || key.value !== null);
+            let canTranspose = Object.entries(config).every(([name, key]) => !isRequired(name, key, virtualTranspose) || key.value !== null);
 
             setIsAbleToTranspose(canTranspose)
             onKeybindSet({config, canTranspose})
         }
-    }, [keysInUse]);
+    }, [keysInUse, virtualTranspose]);
 
     return (
         hasFetchedDefaultConfig &&
@@ -211,9 +309,16 @@ const KeyBindManager = ({onListen = (isListening) => {}, onKeybindSet = (e) => {
             onClick={() => setIsManagerOpen(isAbleToTranspose === false ? true : !isManagerOpen)}
         >
             <SectionCard>
+                <Switch
+                    checked={virtualTranspose}
+                    label={"Virtual Transpose"}
+                    onChange={virtualTransposeHandler}
+                    onClick={(e) => e.stopPropagation()}
+                />
+
                 <span className={"keybind-manager-content"}>
                     {Object.entries(config).map(([name, data]) => (
-                        ((data?.required === undefined || data.required === true) || (data.required === false && isAbleToTranspose))
+                        (isRequired(name, data, virtualTranspose) || isAbleToTranspose)
                         &&
                         <KeyBind
                             value={data.value}
@@ -1,17 +1,21 @@
 import BlankTransposeMatrixItem from "./BlankTransposeMatrixItem.jsx";
 import TransposeMatrixItem from "./TransposeMatrixItem.jsx";
+import {isInPracticeLoop} from "../utils.js";
 
 function NextTransposeItem({data}) {
     if (data?.selectedIndex === undefined || data?.transposes === undefined) return <BlankTransposeMatrixItem/>
 
-    const index = (data.selectedIndex + 1) % data.transposes.length;
+    // next wraps from the end of a practice loop back to its start
+    const index = isInPracticeLoop(data.practiceLoop, data.selectedIndex) && data.selectedIndex === data.practiceLoop.end
+        ? data.practiceLoop.start
+        : (data.selectedIndex + 1) % data.transposes.length;
     const transpose = data.transposes[index];
 
     return (
         <span style={{color: "black"}}>
             {index !== data.selectedIndex && transpose !== undefined
                 ?
-                <TransposeMatrixItem index={index} transpose={transpose} selected={false} />
+                <TransposeMatrixItem index={index} transpose={transpose} label={data.annotations?.[index]?.label} note={data.annotations?.[index]?.note} selected={false} inLoop={isInPracticeLoop(data.practiceLoop, index)} />
                 :
                 <BlankTransposeMatrixItem/>
             }
@@ -1,5 +1,6 @@
 import BlankTransposeMatrixItem from "./BlankTransposeMatrixItem.jsx";
 import TransposeMatrixItem from "./TransposeMatrixItem.jsx";
+import {isInPracticeLoop} from "../utils.js";
 import {useEffect} from "react";
 
 function PrevTransposeItem({data}) {
@@ -12,7 +13,7 @@ function PrevTransposeItem({data}) {
         <span style={{color: "black"}}>
             {index !== data.selectedIndex && transpose !== undefined
                 ?
-                <TransposeMatrixItem index={index} transpose={transpose} selected={false} />
+                <TransposeMatrixItem index={index} transpose={transpose} label={data.annotations?.[index]?.label} note={data.annotations?.[index]?.note} selected={false} inLoop={isInPracticeLoop(data.practiceLoop, index)} />
                 :
                 <BlankTransposeMatrixItem/>
             }
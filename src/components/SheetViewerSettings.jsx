@@ -1,28 +1,32 @@
 import {Drawer, Icon, IconSize, NumericInput, Position, Slider, Text} from "@blueprintjs/core";
 import {useEffect, useState} from "react";
+import {defaultAppDataSettings, getAppDataSettings, migrateLocalStorageSettings, writeAppDataSettings} from "../utils.js";
 
 function SheetViewerSettings({onUpdate = () => {}, isOpen = () => {}}) {
     const [isSettingsOpen, setIsSettingsOpen] = useState(false)
-    const [transparency, setTransparency] = useState(Number(window.localStorage.getItem("transparency")) ?? 50)
-    const [zoomStepSize, setZoomStepSize] = useState(Number(
-        window.localStorage.getItem("zoomConstant")  // i'm stupid ok, don't judge :~)
-        || window.localStorage.getItem("zoomStepSize")) ?? 0.1)
+    const [transparency, setTransparency] = useState(defaultAppDataSettings.window.transparency)
+    const [zoomStepSize, setZoomStepSize] = useState(defaultAppDataSettings.window.zoomStepSize)
 
     const transparencyHandler = (val) => {
         setTransparency(val)
-        window.localStorage.setItem("transparency", val)
+        writeAppDataSettings({window: {transparency: val}})
         onUpdate({transparency: val})
     }
 
     const zoomStepSizeHandler = (val) => {
         if (val === undefined) return;
         setZoomStepSize(val)
-        window.localStorage.setItem("zoomStepSize", val)
+        writeAppDataSettings({window: {zoomStepSize: val}})
         onUpdate({zoomStepSize: val})
     }
 
     useEffect(() => {
-        onUpdate({transparency: transparency, zoomStepSize: zoomStepSize})
+        // the viewer can be opened before the main window got round to it
+        migrateLocalStorageSettings().then(getAppDataSettings).then(settings => {
+            setTransparency(settings.window.transparency)
+            setZoomStepSize(settings.window.zoomStepSize)
+            onUpdate({transparency: settings.window.transparency, zoomStepSize: settings.window.zoomStepSize})
+        })
     }, []);
 
     useEffect(() => {
@@ -1,6 +1,7 @@
 import {InputGroup, Tooltip} from "@blueprintjs/core";
-import {generalAppToastConfig} from "../utils.js";
+import {generalAppToastConfig, toTransposeEntries} from "../utils.js";
 import {emit} from "@tauri-apps/api/event";
+import {invoke} from "@tauri-apps/api";
 import {forwardRef, useEffect, useRef, useState} from "react";
 
 const TransposeInput = forwardRef(
@@ -15,15 +16,29 @@ const TransposeInput = forwardRef(
         ref
     ) => {
         const inputRef = useRef();
+        const [error, setError] = useState(null);
 
-        const getTransposesFromText = (text) => {
-            const regex = /-?\d+/g;
-            const matches = text.match(regex);
+        // whole sheets go by their transpose markers, anything else is a transpose expression like "chorus: (0 2)x2"
+        // text that's neither still gives the numbers in it, the expression's error is shown alongside
+        const getTransposesFromText = async (text) => {
+            const parsed = await invoke("parse_sheet_transposes", {text});
 
-            return matches ? matches.map((match) => parseInt(match, 10)) : [];
+            if (parsed.transposes.length > 0) {
+                return {transposes: parsed.transposes, labels: parsed.markers.map(marker => marker.measure ? `m. ${marker.measure}` : null)};
+            }
+
+            try {
+                return await invoke("parse_transpose_expression", {text});
+            }
+            catch (error) {
+                const list = await invoke("parse_transpose_list", {text});
+                return {transposes: list.transposes, labels: [], error};
+            }
         };
 
-        const sendTransposesHandler = (transposes) => {
+        const sendTransposesHandler = (transposes, labels = []) => {
+            const annotations = labels.map(label => ({label, note: null}));
+
             if (!transposes || transposes.length === 0 || transposes === mainWindowTransposes) {
                 return;
             }
@@ -46,9 +61,9 @@ const TransposeInput = forwardRef(
                 return;
             }
 
-            if (backend) emit("backend_event", { transposes });
+            if (backend) emit("backend_event", { transposes: toTransposeEntries(transposes, annotations) });
 
-            onUpdate(transposes);
+            onUpdate(transposes, annotations);
         };
 
         useEffect(() => {
@@ -66,11 +81,24 @@ const TransposeInput = forwardRef(
             <span className={"transpose-input"}>
                 <Tooltip
                     defaultIsOpen={backend}
-                    content={canTranspose ? "Example: 0 -1 +1 1" : "Keybinds must be set first!"}
+                    content={
+                        !canTranspose
+                        ? "Keybinds must be set first!"
+                        : error
+                        ? `${error.message} (column ${error.column})`
+                        : "Example: 0 -1 +1 1, chorus: (0 2)x2 +1r"
+                    }
                 >
                   <InputGroup
                       id={"transposes-input"}
-                      onInput={(e) => sendTransposesHandler(getTransposesFromText(e.target.value))}
+                      onInput={(e) => getTransposesFromText(e.target.value)
+                          .then(({transposes, labels, error = null}) => {
+                              setError(error)
+                              sendTransposesHandler(transposes, labels)
+                          })
+                          .catch(setError)
+                      }
+                      intent={error ? "danger" : "none"}
                       disabled={!canTranspose}
                       fill={true}
                       leftIcon={"array-numeric"}
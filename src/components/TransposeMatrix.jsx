@@ -1,6 +1,7 @@
 import TransposeMatrixItem from "./TransposeMatrixItem.jsx";
+import {isInPracticeLoop} from "../utils.js";
 
-const TransposeMatrix = ({index, transposes}) => {
+const TransposeMatrix = ({index, transposes, annotations = [], practiceLoop = null}) => {
     const transposesInputEl = document.getElementById("transposes-input")
 
     return (
@@ -21,7 +22,10 @@ const TransposeMatrix = ({index, transposes}) => {
                         <TransposeMatrixItem
                             index={i}
                             transpose={transpose}
+                            label={annotations[i]?.label}
+                            note={annotations[i]?.note}
                             selected={index === i}
+                            inLoop={isInPracticeLoop(practiceLoop, i)}
                             showSelectedToolTip={true}
                         />
                     ))}
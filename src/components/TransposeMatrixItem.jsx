@@ -1,9 +1,11 @@
 import {Card, Tooltip} from "@blueprintjs/core";
 import {emit} from "@tauri-apps/api/event";
 
-const TransposeMatrixItem = ({ transpose, index, selected = false, showSelectedToolTip = false}) => {
+const TransposeMatrixItem = ({ transpose, index, label = null, note = null, selected = false, inLoop = false, showSelectedToolTip = false}) => {
     const translucentIndexColor = selected ? 255 : 0;
 
+    const formatTranspose = (transpose) => transpose === 0 ? "0" : transpose > 0 ? `+${transpose}` : `${transpose}`
+
     const selectIndexHandler = (selected_index) => {
         emit("backend_event", {selected_index})
     }
@@ -24,13 +26,14 @@ const TransposeMatrixItem = ({ transpose, index, selected = false, showSelectedT
         <ToolTipIfSelected selected={selected}>
             <Card
                 id={`transpose-matrix-item-${index}`}
-                className={"transpose-matrix-item"}
+                className={`transpose-matrix-item ${inLoop && !selected ? "transpose-matrix-item-loop" : ""}`}
                 style={{
                     backgroundColor: selected ? "green" : null,
                     color: selected ? "white" : null,
                 }}
                 interactive={true}
                 compact={true}
+                title={note ?? undefined}
                 onClick={() => selectIndexHandler(index)}
             >
                 <span
@@ -41,7 +44,7 @@ const TransposeMatrixItem = ({ transpose, index, selected = false, showSelectedT
                 >
                     {index + 1}
                 </span>
-                <span>{transpose === 0 ? "0" : transpose > 0 ? `+${transpose}` : `${transpose}`}</span>
+                <span>{label ? `${label} (${formatTranspose(transpose)})` : formatTranspose(transpose)}</span>
             </Card>
         </ToolTipIfSelected>
     );
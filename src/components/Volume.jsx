@@ -1,40 +1,31 @@
 import {Button, Slider, Tooltip} from "@blueprintjs/core";
 import {useEffect, useState} from "react";
-import {emit} from "@tauri-apps/api/event";
 import {defaultAppDataSettings, getAppDataSettings, writeAppDataSettings} from "../utils.js";
 
 const Volume = () => {
-    const [muted, setMuted] = useState(defaultAppDataSettings.muted);
-    const [volume, setVolume] = useState(defaultAppDataSettings.volume);
+    const [muted, setMuted] = useState(defaultAppDataSettings.audio.muted);
+    const [volume, setVolume] = useState(defaultAppDataSettings.audio.volume);
 
     const muteHandler = async () => {
         setMuted(!muted);
-        await writeAppDataSettings({muted: !muted});
+        await writeAppDataSettings({audio: {muted: !muted}});
     }
 
     const volumeHandler = async (value) => {
         setVolume(value);
-        await writeAppDataSettings({volume: value});
+        await writeAppDataSettings({audio: {volume: value}});
     }
 
     useEffect(() => {
         const setData = async () => {
             const settings = await getAppDataSettings()
-            setMuted(settings.muted)
-            setVolume(settings.volume)
+            setMuted(settings.audio.muted)
+            setVolume(settings.audio.volume)
         }
 
         setData()
     }, []);
 
-    useEffect(() => {
-        emit("backend_event", {muted});
-    }, [muted]);
-
-    useEffect(() => {
-        emit("backend_event", {volume});
-    }, [volume]);
-
     return (
         <span className={"volume"}>
             <span style={{paddingRight: 12}}>
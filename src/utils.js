@@ -1,12 +1,25 @@
 import {OverlayToaster} from "@blueprintjs/core";
 import {WebviewWindow} from "@tauri-apps/api/window";
-import {appDataDir} from "@tauri-apps/api/path";
-import {exists, readTextFile, writeTextFile} from "@tauri-apps/api/fs";
+import {invoke} from "@tauri-apps/api";
 
-export const defaultAppDataSettings = {muted: false, volume: 0.3};
+export const defaultAppDataSettings = {audio: {muted: false, volume: 0.3}, scroll: {value: 0}, window: {transparency: 5, zoomStepSize: 0.1}};
 export const overlayToasterDefaultProps = {position: "top", maxToasts: 1, canEscapeKeyClear: true}
 export const generalAppToastConfig = {isCloseButtonShown: false, icon: 'key'}
 
+// the backend takes {transpose, label, note} for annotated transposes, plain numbers otherwise
+export const toTransposeEntries = (transposes, annotations = []) => {
+    return transposes.map((transpose, i) =>
+        annotations[i]?.label || annotations[i]?.note || annotations[i]?.time != null ? {transpose, ...annotations[i]} : transpose
+    )
+}
+
+// both ends set, start first, same as the backend's active loop
+export const isInPracticeLoop = (practiceLoop, index) => {
+    return practiceLoop?.start != null && practiceLoop?.end != null
+        && practiceLoop.start <= practiceLoop.end
+        && index >= practiceLoop.start && index <= practiceLoop.end
+}
+
 export function toastOnPause(toaster, paused, canTranspose) {
     toaster.then(toaster => {
         toaster.clear()
@@ -73,28 +86,47 @@ export const preventDefaultEventCallback = (event) => {
 }
 /****/
 
+/** settings are owned by the backend, which validates them and applies what it needs itself **/
 export const getAppDataSettings = async () => {
-    const dataDir = await appDataDir();
-    const settingsPath = `${dataDir}settings.json`
-    const settingsExists = await exists(settingsPath);
-
-    if (!settingsExists) {
-        await writeTextFile(settingsPath, JSON.stringify(defaultAppDataSettings))
+    try {
+        return await invoke("get_settings");
+    }
+    catch (err) {
+        console.error(err);
         return defaultAppDataSettings;
     }
+}
 
-    const contents = await readTextFile(`${dataDir}settings.json`);
-
-    return JSON.parse(contents)
+/** @param patch nested fields to change, e.g. {audio: {muted: true}} **/
+export const writeAppDataSettings = async (patch) => {
+    return invoke("patch_settings", {patch}).catch(err => console.error(err));
 }
 
-export const writeAppDataSettings = async (jsonObj) => {
-    const dataDir = await appDataDir();
-    const settingsPath = `${dataDir}settings.json`
+/** older versions kept these in localStorage, the backend can't see it, so they're moved over once and removed **/
+const legacySettingKeys = ["scrollDownVal", "transparency", "zoomConstant", "zoomStepSize"];
+
+export const migrateLocalStorageSettings = async () => {
+    const storage = window.localStorage;
+    const legacyNumber = (key) => {
+        const value = storage.getItem(key);
+        return value === null || value === "" || isNaN(Number(value)) ? undefined : Number(value);
+    }
+
+    const scrollValue = legacyNumber("scrollDownVal");
+    const transparency = legacyNumber("transparency");
+    const zoomStepSize = legacyNumber("zoomConstant") || legacyNumber("zoomStepSize");
+
+    const patch = {};
+    if (scrollValue !== undefined) patch.scroll = {value: scrollValue};
+    // undefined fields are left out when the patch is sent
+    if (transparency !== undefined || zoomStepSize !== undefined) patch.window = {transparency, zoomStepSize};
 
-    const settings = await getAppDataSettings()
+    if (Object.keys(patch).length > 0) {
+        // a value the backend refuses is dropped, the default stays
+        await writeAppDataSettings(patch);
+    }
 
-    await writeTextFile(settingsPath, JSON.stringify({...settings, ...jsonObj}));
+    legacySettingKeys.forEach(key => storage.removeItem(key));
 }
 
 export function modOrDefault(num, divisor) {